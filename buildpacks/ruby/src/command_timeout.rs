@@ -0,0 +1,512 @@
+use crate::bundler_credentials::Redact;
+use crate::RubyBuildpackError;
+use fun_run::{CmdError, CommandWithName, NamedOutput};
+use libcnb::Env;
+use std::io::{Read, Write};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, ExitStatus, Output, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::{Duration, Instant};
+
+/// Lines of output shown when a command times out
+const TAIL_LINES: usize = 20;
+
+/// How often a running command is checked against its time limit
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long output is read after the command exits, background processes it started
+/// can hold the output open after it exits
+const OUTPUT_GRACE: Duration = Duration::from_secs(1);
+
+/// Build commands that run with a time limit
+///
+/// Each limit can be changed with its environment variable, in seconds. `0` runs the
+/// command without a limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TimedCommand {
+    BundleInstall,
+    BundleLock,
+    RakeDetect,
+    AssetsPrecompile,
+    RakeTasks,
+    BootsnapPrecompile,
+}
+
+impl TimedCommand {
+    pub(crate) fn env_var(self) -> &'static str {
+        match self {
+            TimedCommand::BundleInstall => "HEROKU_BUNDLE_INSTALL_TIMEOUT",
+            TimedCommand::BundleLock => "HEROKU_BUNDLE_LOCK_TIMEOUT",
+            TimedCommand::RakeDetect => "HEROKU_RAKE_DETECT_TIMEOUT",
+            TimedCommand::AssetsPrecompile => "HEROKU_ASSETS_PRECOMPILE_TIMEOUT",
+            TimedCommand::RakeTasks => "HEROKU_RAKE_TASKS_TIMEOUT",
+            TimedCommand::BootsnapPrecompile => "HEROKU_BOOTSNAP_PRECOMPILE_TIMEOUT",
+        }
+    }
+
+    fn default_limit(self) -> Duration {
+        let minutes = match self {
+            TimedCommand::BundleInstall
+            | TimedCommand::AssetsPrecompile
+            | TimedCommand::RakeTasks => 60,
+            TimedCommand::BundleLock
+            | TimedCommand::RakeDetect
+            | TimedCommand::BootsnapPrecompile => 15,
+        };
+        Duration::from_secs(minutes * 60)
+    }
+
+    /// Limit from the command's environment variable, otherwise the default
+    ///
+    /// # Errors
+    ///
+    /// Errors if the environment variable is not a whole number of seconds.
+    pub(crate) fn timeout(self, env: &Env) -> Result<Timeout, RubyBuildpackError> {
+        let limit = match env.get(self.env_var()) {
+            Some(value) => {
+                let value = value.to_string_lossy();
+                match value.trim().parse::<u64>() {
+                    Ok(0) => None,
+                    Ok(seconds) => Some(Duration::from_secs(seconds)),
+                    Err(_) => {
+                        return Err(RubyBuildpackError::InvalidTimeout(
+                            self.env_var().to_string(),
+                            value.to_string(),
+                        ))
+                    }
+                }
+            }
+            None => Some(self.default_limit()),
+        };
+
+        Ok(Timeout {
+            command: self,
+            limit,
+        })
+    }
+}
+
+/// Time limit for a command, `None` runs it until it exits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Timeout {
+    pub(crate) command: TimedCommand,
+    pub(crate) limit: Option<Duration>,
+}
+
+/// A command that was killed after reaching its time limit
+#[derive(Debug)]
+pub(crate) struct CommandTimedOut {
+    pub(crate) name: String,
+    pub(crate) command: TimedCommand,
+    pub(crate) elapsed: Duration,
+    pub(crate) tail: String,
+}
+
+#[derive(Debug)]
+pub(crate) enum TimedCmdError {
+    Cmd(CmdError),
+    TimedOut(CommandTimedOut),
+}
+
+impl TimedCmdError {
+    /// Applies `f` to errors from the command itself, i.e. `fun_run::map_which_problem`
+    pub(crate) fn map_cmd(self, f: impl FnOnce(CmdError) -> CmdError) -> Self {
+        match self {
+            TimedCmdError::Cmd(error) => TimedCmdError::Cmd(f(error)),
+            TimedCmdError::TimedOut(_) => self,
+        }
+    }
+
+    /// Removes credentials from the command output
+    pub(crate) fn redact(self, redact: &Redact) -> Self {
+        match self {
+            TimedCmdError::Cmd(error) => TimedCmdError::Cmd(redact.cmd_error(error)),
+            TimedCmdError::TimedOut(timed_out) => TimedCmdError::TimedOut(CommandTimedOut {
+                name: redact.str(&timed_out.name),
+                tail: redact.str(&timed_out.tail),
+                ..timed_out
+            }),
+        }
+    }
+
+    /// Timeouts become `RubyBuildpackError::CommandTimedOut`, other errors use `f`
+    pub(crate) fn into_buildpack_error(
+        self,
+        f: impl FnOnce(CmdError) -> RubyBuildpackError,
+    ) -> RubyBuildpackError {
+        match self {
+            TimedCmdError::Cmd(error) => f(error),
+            TimedCmdError::TimedOut(timed_out) => RubyBuildpackError::CommandTimedOut(timed_out),
+        }
+    }
+}
+
+/// Same as `CommandWithName::stream_output` with a time limit
+///
+/// # Errors
+///
+/// Errors if the command cannot be run, exits non-zero, or is killed at the time limit.
+pub(crate) fn stream_output<C: CommandWithName>(
+    cmd: &mut C,
+    stdout_write: impl Write,
+    stderr_write: impl Write,
+    timeout: Timeout,
+) -> Result<NamedOutput, TimedCmdError> {
+    let name = cmd.name();
+    let output =
+        output_and_write_streams(cmd.mut_cmd(), &name, stdout_write, stderr_write, timeout)?;
+
+    fun_run::nonzero_streamed(name, output).map_err(TimedCmdError::Cmd)
+}
+
+/// Same as `CommandWithName::named_output` with a time limit
+///
+/// # Errors
+///
+/// Errors if the command cannot be run, exits non-zero, or is killed at the time limit.
+pub(crate) fn named_output<C: CommandWithName>(
+    cmd: &mut C,
+    timeout: Timeout,
+) -> Result<NamedOutput, TimedCmdError> {
+    let name = cmd.name();
+    let output = output_and_write_streams(
+        cmd.mut_cmd(),
+        &name,
+        std::io::sink(),
+        std::io::sink(),
+        timeout,
+    )?;
+
+    fun_run::nonzero_captured(name, output).map_err(TimedCmdError::Cmd)
+}
+
+/// Runs the command in its own process group so it can be killed along with any
+/// processes it started
+fn output_and_write_streams(
+    cmd: &mut Command,
+    name: &str,
+    stdout_write: impl Write,
+    stderr_write: impl Write,
+    timeout: Timeout,
+) -> Result<Output, TimedCmdError> {
+    let system_error =
+        |error| TimedCmdError::Cmd(fun_run::on_system_error(name.to_string(), error));
+
+    let start = Instant::now();
+    let mut child = cmd
+        .process_group(0)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(system_error)?;
+
+    let (sender, receiver) = mpsc::channel();
+    read_output(child.stdout.take(), Chunk::Stdout, sender.clone());
+    read_output(child.stderr.take(), Chunk::Stderr, sender);
+    let mut streams = Streams {
+        receiver,
+        stdout_write,
+        stderr_write,
+        stdout: Vec::new(),
+        stderr: Vec::new(),
+        combined: Vec::new(),
+        open: 2,
+    };
+
+    let status = wait(&mut child, timeout.limit, start, &mut streams);
+    if !matches!(status, Ok(Some(_))) || !streams.finished() {
+        kill_process_group(&child);
+        let _ = child.wait();
+        // A process that left the group, i.e. with `setsid`, can keep the output open
+        let _ = streams.receive_for(OUTPUT_GRACE);
+    }
+
+    match status.map_err(system_error)? {
+        Some(status) => Ok(Output {
+            status,
+            stdout: streams.stdout,
+            stderr: streams.stderr,
+        }),
+        None => Err(TimedCmdError::TimedOut(CommandTimedOut {
+            name: name.to_string(),
+            command: timeout.command,
+            elapsed: start.elapsed(),
+            tail: tail(&streams.combined, TAIL_LINES),
+        })),
+    }
+}
+
+/// Waits for the command to exit and its output to be read, `None` at the time limit
+///
+/// Once the command exits its output is read for up to `OUTPUT_GRACE`, then its status
+/// is returned even if a process it started still holds the output open.
+fn wait(
+    child: &mut Child,
+    limit: Option<Duration>,
+    start: Instant,
+    streams: &mut Streams<impl Write, impl Write>,
+) -> std::io::Result<Option<ExitStatus>> {
+    let mut exited = None;
+    loop {
+        if exited.is_none() {
+            exited = child.try_wait()?.map(|status| (status, Instant::now()));
+        }
+        match exited {
+            Some((status, at)) => {
+                if streams.finished() || at.elapsed() >= OUTPUT_GRACE {
+                    return Ok(Some(status));
+                }
+            }
+            None => {
+                if let Some(limit) = limit {
+                    if start.elapsed() >= limit {
+                        return Ok(None);
+                    }
+                }
+            }
+        }
+        if streams.finished() {
+            std::thread::sleep(POLL_INTERVAL);
+        } else {
+            streams.receive_for(POLL_INTERVAL)?;
+        }
+    }
+}
+
+/// Output sent from a reader thread
+enum Chunk {
+    Stdout(Vec<u8>),
+    Stderr(Vec<u8>),
+    Closed(std::io::Result<()>),
+}
+
+/// Reads output on a thread that is never joined, a process that keeps the output
+/// open cannot stop the build from moving on
+fn read_output(
+    from: Option<impl Read + Send + 'static>,
+    chunk: fn(Vec<u8>) -> Chunk,
+    sender: Sender<Chunk>,
+) {
+    std::thread::spawn(move || {
+        let result = from.map_or(Ok(()), |mut from| {
+            let mut buffer = [0; 8192];
+            loop {
+                match from.read(&mut buffer) {
+                    Ok(0) => return Ok(()),
+                    Ok(len) => {
+                        if sender.send(chunk(buffer[..len].to_vec())).is_err() {
+                            return Ok(());
+                        }
+                    }
+                    Err(error) if error.kind() == std::io::ErrorKind::Interrupted => {}
+                    Err(error) => return Err(error),
+                }
+            }
+        });
+        let _ = sender.send(Chunk::Closed(result));
+    });
+}
+
+/// Writes output as it arrives from the reader threads, keeping everything that was read
+struct Streams<O, E> {
+    receiver: Receiver<Chunk>,
+    stdout_write: O,
+    stderr_write: E,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    combined: Vec<u8>,
+    open: usize,
+}
+
+impl<O: Write, E: Write> Streams<O, E> {
+    fn finished(&self) -> bool {
+        self.open == 0
+    }
+
+    /// Handles output received until both streams close or `duration` passes
+    fn receive_for(&mut self, duration: Duration) -> std::io::Result<()> {
+        let until = Instant::now() + duration;
+        while !self.finished() {
+            let Ok(chunk) = self
+                .receiver
+                .recv_timeout(until.saturating_duration_since(Instant::now()))
+            else {
+                return Ok(());
+            };
+            match chunk {
+                Chunk::Stdout(bytes) => {
+                    self.stdout_write.write_all(&bytes)?;
+                    self.stdout_write.flush()?;
+                    self.stdout.extend_from_slice(&bytes);
+                    self.combined.extend_from_slice(&bytes);
+                }
+                Chunk::Stderr(bytes) => {
+                    self.stderr_write.write_all(&bytes)?;
+                    self.stderr_write.flush()?;
+                    self.stderr.extend_from_slice(&bytes);
+                    self.combined.extend_from_slice(&bytes);
+                }
+                Chunk::Closed(result) => {
+                    self.open -= 1;
+                    result?;
+                }
+            }
+            if Instant::now() >= until {
+                return Ok(());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The command is the leader of its own process group, so its id is the group id
+fn kill_process_group(child: &Child) {
+    if let Ok(pgid) = libc::pid_t::try_from(child.id()) {
+        // SAFETY: `kill` only sends a signal, a group that already exited is an ignored error
+        unsafe {
+            libc::kill(-pgid, libc::SIGKILL);
+        }
+    }
+}
+
+/// Last lines of the output
+fn tail(output: &[u8], lines: usize) -> String {
+    let output = String::from_utf8_lossy(output);
+    let all = output.lines().collect::<Vec<_>>();
+    all[all.len().saturating_sub(lines)..].join("\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn timeout(limit: Duration) -> Timeout {
+        Timeout {
+            command: TimedCommand::RakeTasks,
+            limit: Some(limit),
+        }
+    }
+
+    #[test]
+    fn test_timeout_from_env() {
+        let mut env = Env::new();
+        assert_eq!(
+            Some(Duration::from_secs(15 * 60)),
+            TimedCommand::RakeDetect.timeout(&env).unwrap().limit
+        );
+
+        env.insert("HEROKU_RAKE_DETECT_TIMEOUT", "90");
+        assert_eq!(
+            Some(Duration::from_secs(90)),
+            TimedCommand::RakeDetect.timeout(&env).unwrap().limit
+        );
+
+        env.insert("HEROKU_RAKE_DETECT_TIMEOUT", "0");
+        assert_eq!(None, TimedCommand::RakeDetect.timeout(&env).unwrap().limit);
+
+        env.insert("HEROKU_RAKE_DETECT_TIMEOUT", "15m");
+        assert!(TimedCommand::RakeDetect.timeout(&env).is_err());
+    }
+
+    #[test]
+    fn test_command_finishes() {
+        let mut cmd = Command::new("bash");
+        cmd.args(["-c", "echo hello; echo oops >&2"]);
+        let mut stdout = Vec::new();
+
+        let output = stream_output(
+            &mut cmd,
+            &mut stdout,
+            std::io::sink(),
+            timeout(Duration::from_secs(10)),
+        )
+        .unwrap();
+        assert_eq!("hello\n", output.stdout_lossy());
+        assert_eq!("oops\n", output.stderr_lossy());
+        assert_eq!(b"hello\n".to_vec(), stdout);
+
+        let mut cmd = Command::new("bash");
+        cmd.args(["-c", "exit 1"]);
+        assert!(matches!(
+            named_output(&mut cmd, timeout(Duration::from_secs(10))),
+            Err(TimedCmdError::Cmd(CmdError::NonZeroExitNotStreamed(_)))
+        ));
+    }
+
+    #[test]
+    fn test_command_times_out() {
+        let mut cmd = Command::new("bash");
+        cmd.args([
+            "-c",
+            "for i in 1 2 3; do echo line $i; done; sleep 30 & wait",
+        ]);
+
+        let start = Instant::now();
+        let Err(TimedCmdError::TimedOut(timed_out)) =
+            named_output(&mut cmd, timeout(Duration::from_millis(500)))
+        else {
+            panic!("Expected command to time out")
+        };
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert!(timed_out.elapsed >= Duration::from_millis(500));
+        assert_eq!("line 1\nline 2\nline 3", timed_out.tail);
+    }
+
+    #[test]
+    fn test_command_exits_with_background_process() {
+        let mut cmd = Command::new("bash");
+        cmd.args(["-c", "echo done; sleep 30 &"]);
+
+        let start = Instant::now();
+        let output = named_output(
+            &mut cmd,
+            Timeout {
+                command: TimedCommand::RakeTasks,
+                limit: None,
+            },
+        )
+        .unwrap();
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert_eq!("done\n", output.stdout_lossy());
+
+        let mut cmd = Command::new("bash");
+        cmd.args(["-c", "echo done; sleep 30 &"]);
+        assert!(named_output(&mut cmd, timeout(Duration::from_millis(500))).is_ok());
+    }
+
+    #[test]
+    fn test_command_exits_with_process_outside_its_group() {
+        let mut cmd = Command::new("bash");
+        cmd.args(["-c", "echo done; setsid sleep 30 &"]);
+
+        let start = Instant::now();
+        let output = named_output(
+            &mut cmd,
+            Timeout {
+                command: TimedCommand::RakeTasks,
+                limit: None,
+            },
+        )
+        .unwrap();
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert_eq!("done\n", output.stdout_lossy());
+
+        let mut cmd = Command::new("bash");
+        cmd.args(["-c", "echo line; setsid sleep 30 & wait"]);
+
+        let start = Instant::now();
+        let Err(TimedCmdError::TimedOut(timed_out)) =
+            named_output(&mut cmd, timeout(Duration::from_millis(500)))
+        else {
+            panic!("Expected command to time out")
+        };
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert_eq!("line", timed_out.tail);
+    }
+
+    #[test]
+    fn test_tail() {
+        assert_eq!("c\nd", tail(b"a\nb\nc\nd\n", 2));
+        assert_eq!("a", tail(b"a", 2));
+    }
+}
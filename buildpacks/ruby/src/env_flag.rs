@@ -0,0 +1,41 @@
+use libcnb::Env;
+
+/// Values that turn on an opt-in environment variable, compared ignoring case
+const ENABLED_VALUES: [&str; 4] = ["1", "true", "yes", "on"];
+
+/// Whether a user configured environment variable turns a feature on
+///
+/// Only `1`, `true`, `yes` or `on` enable it, so `HEROKU_GEM_AUDIT=0` or
+/// `HEROKU_GEM_AUDIT=false` leave the feature off.
+pub(crate) fn enabled(env: &Env, key: &str) -> bool {
+    match env.get(key) {
+        Some(value) => {
+            let value = value.to_string_lossy();
+            ENABLED_VALUES
+                .iter()
+                .any(|enabled| value.trim().eq_ignore_ascii_case(enabled))
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_enabled() {
+        let mut env = Env::new();
+        assert!(!enabled(&env, "HEROKU_FEATURE"));
+
+        for value in ["1", "true", "TRUE", "yes", "on"] {
+            env.insert("HEROKU_FEATURE", value);
+            assert!(enabled(&env, "HEROKU_FEATURE"), "{value}");
+        }
+
+        for value in ["", "0", "false", "False", "no", "off"] {
+            env.insert("HEROKU_FEATURE", value);
+            assert!(!enabled(&env, "HEROKU_FEATURE"), "{value}");
+        }
+    }
+}
@@ -0,0 +1,267 @@
+use commons::cache::{mib, CacheConfig, KeepPath, OverLimit};
+use commons::gem_version::GemVersion;
+use commons::gemfile_lock::GemfileLock;
+use core::str::FromStr;
+use std::path::Path;
+
+/// Web framework detected from the `Gemfile.lock`
+///
+/// Each framework contributes its own defaults on top of the ones every Ruby app
+/// gets, so that an app only sees settings it reads. For example
+/// `RAILS_SERVE_STATIC_FILES` is only set for Rails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Framework {
+    /// Rails, by the locked version of `railties`
+    Rails {
+        version: String,
+    },
+    Hanami,
+    Sinatra,
+    Roda,
+    Rack,
+}
+
+/// How a framework compiles assets for production
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum AssetTask {
+    /// `rake assets:precompile`, and `assets:clean` when defined, found via `rake -P`
+    Rake,
+
+    /// Framework command run via `bundle exec`
+    Command(&'static [&'static str]),
+}
+
+impl AssetTask {
+    /// The framework's asset task, `rake assets:precompile` when none is detected
+    pub(crate) fn for_framework(framework: Option<&Framework>) -> AssetTask {
+        framework.map_or(AssetTask::Rake, Framework::asset_task)
+    }
+}
+
+impl Framework {
+    /// Detects the framework from locked gems, in order of precedence
+    pub(crate) fn detect(gemfile_lock: &GemfileLock) -> Option<Framework> {
+        let version_for = |name: &str| {
+            gemfile_lock
+                .specs
+                .iter()
+                .find(|spec| spec.name == name)
+                .map(|spec| spec.version.clone())
+        };
+
+        if let Some(version) = version_for("railties") {
+            Some(Framework::Rails { version })
+        } else if version_for("hanami").is_some() {
+            Some(Framework::Hanami)
+        } else if version_for("sinatra").is_some() {
+            Some(Framework::Sinatra)
+        } else if version_for("roda").is_some() {
+            Some(Framework::Roda)
+        } else if version_for("rack").is_some() {
+            Some(Framework::Rack)
+        } else {
+            None
+        }
+    }
+
+    /// Name of the gem that identifies the framework
+    pub(crate) fn gem(&self) -> &'static str {
+        match self {
+            Framework::Rails { .. } => "railties",
+            Framework::Hanami => "hanami",
+            Framework::Sinatra => "sinatra",
+            Framework::Roda => "roda",
+            Framework::Rack => "rack",
+        }
+    }
+
+    /// Default environment variables, applied only when the user has not set them
+    pub(crate) fn env(&self) -> Vec<(&'static str, &'static str)> {
+        match self {
+            Framework::Rails { .. } => vec![
+                ("RAILS_ENV", "production"),
+                ("RAILS_SERVE_STATIC_FILES", "enabled"),
+                ("RAILS_LOG_TO_STDOUT", "enabled"),
+                ("DISABLE_SPRING", "1"),
+            ],
+            Framework::Hanami => vec![("HANAMI_ENV", "production")],
+            Framework::Sinatra => vec![("APP_ENV", "production")],
+            Framework::Roda | Framework::Rack => Vec::new(),
+        }
+    }
+
+    /// How assets are compiled
+    ///
+    /// Frameworks without their own asset command use `rake assets:precompile`, which
+    /// only runs when `rake -P` lists it, i.e. Sinatra apps using `sprockets`.
+    pub(crate) fn asset_task(&self) -> AssetTask {
+        match self {
+            Framework::Hanami => AssetTask::Command(&["hanami", "assets", "compile"]),
+            Framework::Rails { .. } | Framework::Sinatra | Framework::Roda | Framework::Rack => {
+                AssetTask::Rake
+            }
+        }
+    }
+
+    /// Environment variables set only while compiling assets
+    ///
+    /// Rails 7.1 skips loading credentials and `secret_key_base` with
+    /// `SECRET_KEY_BASE_DUMMY`, so assets compile without production secrets.
+    pub(crate) fn precompile_env(&self) -> Vec<(&'static str, &'static str)> {
+        match self {
+            Framework::Rails { version } if !is_before(version, "7.1") => {
+                vec![("SECRET_KEY_BASE_DUMMY", "1")]
+            }
+            Framework::Rails { .. }
+            | Framework::Hanami
+            | Framework::Sinatra
+            | Framework::Roda
+            | Framework::Rack => Vec::new(),
+        }
+    }
+
+    /// Web command used when no app server is detected
+    ///
+    /// Run directly without a shell, the launcher expands `$(PORT)` in arguments.
+    /// Rails before 4.0 did not generate a `bin/rails` binstub.
+    pub(crate) fn web_command(&self) -> Vec<&'static str> {
+        match self {
+            Framework::Rails { version } if is_before(version, "4.0") => vec![
+                "bundle",
+                "exec",
+                "rails",
+                "server",
+                "--port",
+                "$(PORT)",
+                "--environment",
+                "$(RAILS_ENV)",
+            ],
+            Framework::Rails { .. } => vec![
+                "bin/rails",
+                "server",
+                "--port",
+                "$(PORT)",
+                "--environment",
+                "$(RAILS_ENV)",
+            ],
+            Framework::Hanami | Framework::Sinatra | Framework::Roda | Framework::Rack => vec![
+                "bundle", "exec", "rackup", "--port", "$(PORT)", "--host", "0.0.0.0",
+            ],
+        }
+    }
+
+    /// Directories cached between builds when compiling assets into `assets_dir`
+    ///
+    /// Hanami compiles assets without an intermediate cache directory.
+    pub(crate) fn asset_cache(&self, app_dir: &Path, assets_dir: &Path) -> Vec<CacheConfig> {
+        let public_assets = CacheConfig {
+            path: assets_dir.to_path_buf(),
+            limit: mib(100),
+            over_limit: OverLimit::RemoveOldest,
+            keep_path: KeepPath::Runtime,
+            invalidate_on: Vec::new(),
+        };
+        match self {
+            Framework::Hanami => vec![public_assets],
+            Framework::Rails { .. } | Framework::Sinatra | Framework::Roda | Framework::Rack => {
+                vec![
+                    public_assets,
+                    CacheConfig {
+                        path: app_dir.join("tmp").join("cache").join("assets"),
+                        limit: mib(100),
+                        over_limit: OverLimit::RemoveOldest,
+                        keep_path: KeepPath::BuildOnly,
+                        invalidate_on: Vec::new(),
+                    },
+                ]
+            }
+        }
+    }
+}
+
+fn is_before(version: &str, other: &str) -> bool {
+    match (GemVersion::from_str(version), GemVersion::from_str(other)) {
+        (Ok(version), Ok(other)) => version < other,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn lockfile(specs: &str) -> GemfileLock {
+        GemfileLock::from_str(&format!(
+            "GEM\n  remote: https://rubygems.org/\n  specs:\n{specs}\nPLATFORMS\n  x86_64-linux\n"
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(
+            Some(Framework::Rails {
+                version: String::from("7.1.2")
+            }),
+            Framework::detect(&lockfile("    rack (3.0.8)\n    railties (7.1.2)\n"))
+        );
+        assert_eq!(
+            Some(Framework::Sinatra),
+            Framework::detect(&lockfile("    rack (3.0.8)\n    sinatra (3.1.0)\n"))
+        );
+        assert_eq!(
+            Some(Framework::Rack),
+            Framework::detect(&lockfile("    rack (3.0.8)\n"))
+        );
+        assert_eq!(None, Framework::detect(&lockfile("    rake (13.0.6)\n")));
+    }
+
+    #[test]
+    fn test_rails_profile() {
+        let rails = Framework::Rails {
+            version: String::from("7.1.2"),
+        };
+        assert_eq!("bin/rails", rails.web_command()[0]);
+        assert!(rails
+            .env()
+            .contains(&("RAILS_SERVE_STATIC_FILES", "enabled")));
+
+        let rails = Framework::Rails {
+            version: String::from("3.2.22"),
+        };
+        assert_eq!(
+            vec!["bundle", "exec", "rails", "server"],
+            rails.web_command()[..4]
+        );
+
+        assert!(rails.precompile_env().is_empty());
+        assert_eq!(
+            vec![("SECRET_KEY_BASE_DUMMY", "1")],
+            Framework::Rails {
+                version: String::from("7.1.0")
+            }
+            .precompile_env()
+        );
+
+        assert!(Framework::Rack
+            .env()
+            .iter()
+            .all(|(key, _)| !key.starts_with("RAILS_")));
+        assert!(rails.env().contains(&("RAILS_ENV", "production")));
+    }
+
+    #[test]
+    fn test_asset_task() {
+        let rails = Framework::Rails {
+            version: String::from("7.1.2"),
+        };
+        assert_eq!(AssetTask::Rake, rails.asset_task());
+        assert_eq!(
+            AssetTask::Command(&["hanami", "assets", "compile"]),
+            Framework::Hanami.asset_task()
+        );
+        assert_eq!(AssetTask::Rake, Framework::Sinatra.asset_task());
+        assert_eq!(AssetTask::Rake, Framework::Rack.asset_task());
+        assert_eq!(AssetTask::Rake, AssetTask::for_framework(None));
+    }
+}
@@ -0,0 +1,373 @@
+use crate::gem_list::GemList;
+use commons::gem_requirement::GemRequirement;
+use commons::gem_version::GemVersion;
+use core::str::FromStr;
+use serde::Deserialize;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// A security advisory in the `ruby-advisory-db` format
+///
+/// Advisories are stored one per file at `gems/<gem-name>/<id>.yml`:
+///
+/// ```yaml
+/// gem: rack
+/// cve: 2022-30123
+/// title: Possible shell escape sequence injection vulnerability in Rack
+/// cvss_v3: 10.0
+/// patched_versions:
+///   - "~> 2.0.9, >= 2.0.9.1"
+///   - ">= 2.2.3.1"
+/// ```
+///
+/// - Repo: <https://github.com/rubysec/ruby-advisory-db>
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub(crate) struct Advisory {
+    pub(crate) gem: String,
+    pub(crate) cve: Option<String>,
+    pub(crate) ghsa: Option<String>,
+    pub(crate) url: Option<String>,
+    pub(crate) title: Option<String>,
+    pub(crate) cvss_v2: Option<f64>,
+    pub(crate) cvss_v3: Option<f64>,
+    #[serde(default)]
+    pub(crate) patched_versions: Vec<String>,
+    #[serde(default)]
+    pub(crate) unaffected_versions: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Severity {
+    Unknown,
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Unknown => "unknown",
+            Severity::Low => "low",
+            Severity::Medium => "medium",
+            Severity::High => "high",
+            Severity::Critical => "critical",
+        })
+    }
+}
+
+impl Advisory {
+    /// Severity from the CVSS score, preferring v3 over v2
+    pub(crate) fn severity(&self) -> Severity {
+        match (self.cvss_v3, self.cvss_v2) {
+            (Some(score), _) if score >= 9.0 => Severity::Critical,
+            (Some(score), _) if score >= 7.0 => Severity::High,
+            (Some(score), _) if score >= 4.0 => Severity::Medium,
+            (Some(score), _) if score > 0.0 => Severity::Low,
+            (None, Some(score)) if score >= 7.0 => Severity::High,
+            (None, Some(score)) if score >= 4.0 => Severity::Medium,
+            (None, Some(score)) if score > 0.0 => Severity::Low,
+            _ => Severity::Unknown,
+        }
+    }
+
+    /// Identifier used in output, i.e. `CVE-2022-30123`
+    pub(crate) fn id(&self) -> String {
+        self.cve
+            .as_ref()
+            .map(|cve| format!("CVE-{cve}"))
+            .or_else(|| self.ghsa.as_ref().map(|ghsa| format!("GHSA-{ghsa}")))
+            .unwrap_or_else(|| String::from("(no identifier)"))
+    }
+
+    /// First patched or unaffected requirement that cannot be parsed
+    fn invalid_requirement(&self) -> Option<&String> {
+        self.patched_versions
+            .iter()
+            .chain(self.unaffected_versions.iter())
+            .find(|requirement| GemRequirement::from_str(requirement).is_err())
+    }
+
+    /// A version is vulnerable unless it's patched or was never affected
+    ///
+    /// Advisories with requirements that cannot be parsed are rejected by
+    /// `load_advisories`, otherwise every version would be reported.
+    fn is_vulnerable(&self, version: &GemVersion) -> bool {
+        !self
+            .patched_versions
+            .iter()
+            .chain(self.unaffected_versions.iter())
+            .filter_map(|requirement| GemRequirement::from_str(requirement).ok())
+            .any(|requirement| requirement.satisfied_by(version))
+    }
+}
+
+/// A locked gem matching an advisory
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Finding {
+    pub(crate) name: String,
+    pub(crate) version: String,
+    pub(crate) advisory: Advisory,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({}) {} severity {}",
+            self.name,
+            self.version,
+            self.advisory.severity(),
+            self.advisory.id()
+        )?;
+        if let Some(title) = &self.advisory.title {
+            write!(f, ": {}", title.trim())?;
+        }
+        if !self.advisory.patched_versions.is_empty() {
+            write!(
+                f,
+                " (patched: {})",
+                self.advisory.patched_versions.join("; ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub(crate) enum GemAuditError {
+    #[error("Could not read advisory database directory {0}: {1}")]
+    ReadDir(PathBuf, std::io::Error),
+
+    #[error("Could not read advisory {0}: {1}")]
+    ReadFile(PathBuf, std::io::Error),
+
+    #[error("Could not parse advisory {0}: {1}")]
+    Parse(PathBuf, serde_yaml::Error),
+
+    #[error("Could not parse version requirement {1:?} in advisory {0}")]
+    InvalidRequirement(PathBuf, String),
+}
+
+/// Loads advisories from a `ruby-advisory-db` checkout for the given gems only
+///
+/// Gem names are matched to the `gems/<gem-name>` directories ignoring case, as gem
+/// names are stored lowercase in the `GemList` while the database uses the name as
+/// published i.e. `gems/RedCloth`.
+///
+/// # Errors
+///
+/// Errors if an advisory file for one of the gems cannot be read or parsed, or has a
+/// version requirement that cannot be parsed.
+pub(crate) fn load_advisories(
+    database: &Path,
+    gem_list: &GemList,
+) -> Result<Vec<Advisory>, GemAuditError> {
+    let gems_dir = database.join("gems");
+    let mut dirs = fs_err::read_dir(&gems_dir)
+        .map_err(|error| GemAuditError::ReadDir(gems_dir.clone(), error))?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .filter(|path| match path.file_name() {
+            Some(name) => gem_list
+                .gems
+                .contains_key(&name.to_string_lossy().to_lowercase()),
+            None => false,
+        })
+        .collect::<Vec<_>>();
+    dirs.sort();
+
+    let mut advisories = Vec::new();
+    for dir in dirs {
+        let mut paths = fs_err::read_dir(&dir)
+            .map_err(|error| GemAuditError::ReadDir(dir.clone(), error))?
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().map_or(false, |ext| ext == "yml"))
+            .collect::<Vec<_>>();
+        paths.sort();
+
+        for path in paths {
+            let contents = fs_err::read_to_string(&path)
+                .map_err(|error| GemAuditError::ReadFile(path.clone(), error))?;
+            let advisory = serde_yaml::from_str::<Advisory>(&contents)
+                .map_err(|error| GemAuditError::Parse(path.clone(), error))?;
+            if let Some(requirement) = advisory.invalid_requirement() {
+                return Err(GemAuditError::InvalidRequirement(path, requirement.clone()));
+            }
+            advisories.push(advisory);
+        }
+    }
+
+    Ok(advisories)
+}
+
+/// Returns a finding for each locked gem version affected by an advisory
+pub(crate) fn audit(gem_list: &GemList, advisories: &[Advisory]) -> Vec<Finding> {
+    let mut findings = advisories
+        .iter()
+        .filter_map(|advisory| {
+            let name = advisory.gem.to_lowercase();
+            gem_list
+                .gems
+                .get(&name)
+                .filter(|entry| advisory.is_vulnerable(&entry.version))
+                .map(|entry| Finding {
+                    name,
+                    version: entry.version.to_string(),
+                    advisory: advisory.clone(),
+                })
+        })
+        .collect::<Vec<_>>();
+    findings.sort_by(|a, b| {
+        b.advisory
+            .severity()
+            .cmp(&a.advisory.severity())
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    findings
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const RACK_ADVISORY: &str = r#"
+---
+gem: rack
+cve: 2022-30123
+title: Possible shell escape sequence injection vulnerability in Rack
+date: 2022-05-27
+description: |
+  Carefully crafted requests can cause shell escape sequences to be written
+  to the terminal via Rack's lint middleware and CommonLogger middleware.
+cvss_v3: 10.0
+patched_versions:
+  - "~> 2.0.9, >= 2.0.9.1"
+  - "~> 2.1.4, >= 2.1.4.1"
+  - ">= 2.2.3.1"
+"#;
+
+    fn gem_list(entries: &str) -> GemList {
+        GemList::from_str(&format!("Gems included by the bundle:\n{entries}")).unwrap()
+    }
+
+    #[test]
+    fn test_audit() {
+        let advisory = serde_yaml::from_str::<Advisory>(RACK_ADVISORY).unwrap();
+        assert_eq!(Severity::Critical, advisory.severity());
+        assert_eq!("CVE-2022-30123", advisory.id());
+
+        let findings = audit(
+            &gem_list("  * rack (2.2.3)\n"),
+            std::slice::from_ref(&advisory),
+        );
+        assert_eq!(1, findings.len());
+        assert!(findings[0]
+            .to_string()
+            .starts_with("rack (2.2.3) critical severity CVE-2022-30123"));
+
+        assert!(audit(
+            &gem_list("  * rack (2.2.3.1)\n"),
+            std::slice::from_ref(&advisory)
+        )
+        .is_empty());
+        assert!(audit(
+            &gem_list("  * rack (2.1.4.1)\n"),
+            std::slice::from_ref(&advisory)
+        )
+        .is_empty());
+        assert_eq!(
+            1,
+            audit(
+                &gem_list("  * rack (2.1.3)\n"),
+                std::slice::from_ref(&advisory)
+            )
+            .len()
+        );
+        assert!(audit(&gem_list("  * puma (6.0.0)\n"), &[advisory]).is_empty());
+    }
+
+    #[test]
+    fn test_unaffected_versions() {
+        let advisory = Advisory {
+            gem: String::from("puma"),
+            cve: None,
+            ghsa: Some(String::from("abcd-1234-efgh")),
+            url: None,
+            title: None,
+            cvss_v2: Some(5.0),
+            cvss_v3: None,
+            patched_versions: vec![String::from(">= 6.0.1")],
+            unaffected_versions: vec![String::from("< 5.0")],
+        };
+        assert_eq!(Severity::Medium, advisory.severity());
+        assert_eq!("GHSA-abcd-1234-efgh", advisory.id());
+
+        assert!(audit(
+            &gem_list("  * puma (4.3.0)\n"),
+            std::slice::from_ref(&advisory)
+        )
+        .is_empty());
+        assert_eq!(1, audit(&gem_list("  * puma (5.6.0)\n"), &[advisory]).len());
+    }
+
+    #[test]
+    fn test_load_advisories() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("gems").join("rack");
+        fs_err::create_dir_all(&dir).unwrap();
+        fs_err::write(dir.join("CVE-2022-30123.yml"), RACK_ADVISORY).unwrap();
+        fs_err::write(dir.join("README.md"), "not an advisory").unwrap();
+
+        let advisories = load_advisories(
+            tmp.path(),
+            &gem_list("  * rack (2.2.3)\n  * puma (6.0.0)\n"),
+        )
+        .unwrap();
+        assert_eq!(1, advisories.len());
+        assert_eq!("rack", advisories[0].gem);
+
+        fs_err::write(dir.join("broken.yml"), "gem: [").unwrap();
+        assert!(matches!(
+            load_advisories(tmp.path(), &gem_list("  * rack (2.2.3)\n")),
+            Err(GemAuditError::Parse(_, _))
+        ));
+    }
+
+    #[test]
+    fn test_load_advisories_mixed_case() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("gems").join("RedCloth");
+        fs_err::create_dir_all(&dir).unwrap();
+        fs_err::write(
+            dir.join("CVE-2012-6684.yml"),
+            "gem: RedCloth\ncve: 2012-6684\npatched_versions:\n  - \">= 4.3.0\"\n",
+        )
+        .unwrap();
+
+        let gems = gem_list("  * RedCloth (4.2.9)\n");
+        let advisories = load_advisories(tmp.path(), &gems).unwrap();
+        assert_eq!(1, advisories.len());
+        assert_eq!(1, audit(&gems, &advisories).len());
+    }
+
+    #[test]
+    fn test_load_advisories_invalid_requirement() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("gems").join("rack");
+        fs_err::create_dir_all(&dir).unwrap();
+        fs_err::write(
+            dir.join("CVE-2022-30123.yml"),
+            "gem: rack\npatched_versions:\n  - \"not a version\"\n",
+        )
+        .unwrap();
+
+        assert!(matches!(
+            load_advisories(tmp.path(), &gem_list("  * rack (2.2.3)\n")),
+            Err(GemAuditError::InvalidRequirement(_, requirement)) if requirement == "not a version"
+        ));
+    }
+}
@@ -1,21 +1,94 @@
-use commons::gem_version::GemVersion;
+use crate::BundleWithout;
+use commons::gem_version::{GemVersion, VersionError};
+use commons::gemfile_lock::{GemfileLock, LockedSpec, ResolvedBundlerVersion};
 use commons::output::{
     fmt,
-    section_log::{log_step_timed, SectionLogger},
+    section_log::{log_step, log_step_timed, SectionLogger},
 };
 use core::str::FromStr;
 use fun_run::{CmdError, CommandWithName};
+use libcnb::Env;
 use regex::Regex;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
 /// ## Gets list of an application's dependencies
 ///
 /// Requires `ruby` and `bundle` to be installed and on the PATH
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct GemList {
-    pub gems: HashMap<String, GemVersion>,
+    pub gems: HashMap<String, GemEntry>,
+}
+
+/// A single resolved gem
+///
+/// Platform specific gems such as `nokogiri (1.15.4-x86_64-linux)` record the platform
+/// separately from the version. Gems installed from git such as `rails (7.1.0 abc1234)`
+/// record the short revision.
+#[derive(Debug, PartialEq)]
+pub struct GemEntry {
+    pub version: GemVersion,
+    pub platform: Option<String>,
+    pub revision: Option<String>,
+}
+
+/// Operating systems and architectures used in rubygems platform names
+///
+/// Used to tell a platform suffix (`1.15.4-x86_64-linux`) apart from a
+/// prerelease version that contains a dash (`1.0.0-rc1`).
+const PLATFORM_PARTS: [&str; 11] = [
+    "linux",
+    "darwin",
+    "mingw",
+    "mswin",
+    "java",
+    "jruby",
+    "freebsd",
+    "openbsd",
+    "solaris",
+    "cygwin",
+    "universal",
+];
+
+impl std::fmt::Display for GemEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.version)?;
+        if let Some(platform) = &self.platform {
+            write!(f, "-{platform}")?;
+        }
+        if let Some(revision) = &self.revision {
+            write!(f, " {revision}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for GemEntry {
+    type Err = VersionError;
+
+    /// Parses the contents of the parens in a `bundle list` entry i.e. `1.15.4-x86_64-linux`
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        let mut parts = string.split_whitespace();
+        let version = parts.next().unwrap_or_default();
+        let revision = parts.next().map(String::from);
+
+        let (version, platform) = match version.split_once('-') {
+            Some((number, platform))
+                if PLATFORM_PARTS.iter().any(|part| platform.contains(part)) =>
+            {
+                (number, Some(platform.to_string()))
+            }
+            _ => (version, None),
+        };
+
+        Ok(GemEntry {
+            version: GemVersion::from_str(version)?,
+            platform,
+            revision,
+        })
+    }
 }
 
 /// Converts the output of `$ gem list` into a data structure that can be inspected and compared
@@ -54,6 +127,107 @@ pub struct GemList {
 ///         );
 /// ```
 impl GemList {
+    /// Reads gems from the `Gemfile.lock` when possible, otherwise calls `bundle list`
+    ///
+    /// Requires `env` to contain the bundler configuration used for `bundle install`.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the lockfile cannot be used and the command `bundle list` is unsuccessful.
+    pub(crate) fn from_lockfile_or_bundle_list(
+        logger: &dyn SectionLogger,
+        app_dir: &Path,
+        gemfile_lock: &GemfileLock,
+        bundler_version: &ResolvedBundlerVersion,
+        env: &Env,
+    ) -> Result<Self, CmdError> {
+        let gemfile_lock_name = fmt::value("Gemfile.lock");
+        let gemfile = env
+            .get("BUNDLE_GEMFILE")
+            .map_or_else(|| app_dir.join("Gemfile"), PathBuf::from);
+        let result = if env.get("BUNDLE_WITH").is_some() || env.get("BUNDLE_ONLY").is_some() {
+            Err(LockfileGemsError::Unsupported(String::from(
+                "BUNDLE_WITH or BUNDLE_ONLY",
+            )))
+        } else {
+            fs_err::read_to_string(gemfile)
+                .map_err(|_| LockfileGemsError::MissingGemfile)
+                .and_then(|gemfile| {
+                    GemList::from_lockfile(
+                        gemfile_lock,
+                        &gemfile,
+                        &env.get("BUNDLE_WITHOUT")
+                            .map(|without| without.to_string_lossy().to_string())
+                            .unwrap_or_default(),
+                        &crate::steps::build_platform(),
+                        bundler_version,
+                    )
+                })
+        };
+
+        match result {
+            Ok(gem_list) => {
+                log_step(format!("Reading gems from {gemfile_lock_name}"));
+                Ok(gem_list)
+            }
+            Err(error) => {
+                log_step(format!(
+                    "Skipping reading gems from {gemfile_lock_name} ({error})"
+                ));
+                GemList::from_bundle_list(env, logger)
+            }
+        }
+    }
+
+    /// Returns the gems `bundle install` installed without running `bundle`
+    ///
+    /// Bundler does not record groups in the `Gemfile.lock`. They're read from the
+    /// `Gemfile` which must only use a static subset of the DSL. Gems listed in
+    /// `DEPENDENCIES` that are only in groups from `without` are skipped along with any
+    /// dependency not needed by another gem. Gems for the given platform are preferred
+    /// over the generic `ruby` platform. The result matches `bundle list`.
+    ///
+    /// # Errors
+    ///
+    /// Errors when the `Gemfile` uses features that cannot be evaluated statically
+    /// or the `Gemfile.lock` is missing a gem.
+    pub(crate) fn from_lockfile(
+        gemfile_lock: &GemfileLock,
+        gemfile: &str,
+        without: &str,
+        platform: &str,
+        bundler_version: &ResolvedBundlerVersion,
+    ) -> Result<Self, LockfileGemsError> {
+        let mut gems = installed_specs(gemfile_lock, gemfile, without, platform)?
+            .into_iter()
+            .map(|spec| {
+                (
+                    spec.name.to_lowercase(),
+                    GemEntry {
+                        version: GemVersion::from_str(&spec.version).unwrap_or_default(),
+                        platform: spec.platform.clone(),
+                        // `bundle list` shows the short revision
+                        revision: spec
+                            .revision
+                            .as_ref()
+                            .map(|revision| revision.chars().take(7).collect()),
+                    },
+                )
+            })
+            .collect::<HashMap<_, _>>();
+        // Bundler is not included in the lockfile specs
+        gems.insert(
+            String::from("bundler"),
+            GemEntry {
+                version: GemVersion::from_str(&bundler_version.to_string()).unwrap_or_default(),
+                platform: None,
+                revision: None,
+            },
+        );
+
+        Ok(GemList { gems })
+    }
+
     /// Calls `bundle list` and returns a `GemList` struct
     ///
     /// # Errors
@@ -79,41 +253,360 @@ impl GemList {
     pub(crate) fn has(&self, str: &str) -> bool {
         self.gems.get(&str.trim().to_lowercase()).is_some()
     }
+
+    #[must_use]
+    pub(crate) fn version_for(&self, str: &str) -> Option<&GemVersion> {
+        self.gems
+            .get(&str.trim().to_lowercase())
+            .map(|entry| &entry.version)
+    }
 }
 
 impl FromStr for GemList {
     type Err = CmdError;
 
     fn from_str(string: &str) -> Result<Self, Self::Err> {
-        // https://regex101.com/r/EIJe5G/1
-        let gem_entry_re = Regex::new("  \\* (\\S+) \\(([a-zA-Z0-9\\.]+)\\)")
-            .expect("Internal error: invalid regex");
+        let gem_entry_re =
+            Regex::new("  \\* (\\S+) \\(([^)]+)\\)").expect("Internal error: invalid regex");
 
         let gems = gem_entry_re
             .captures_iter(string)
-            .map(
-                |capture| {
-                    let name = match capture.get(1) {
-                        Some(m) => m.as_str(),
-                        None => "",
-                    };
+            .map(|capture| {
+                let name = capture.get(1).map_or("", |m| m.as_str());
+                let entry = capture
+                    .get(2)
+                    .and_then(|m| GemEntry::from_str(m.as_str()).ok())
+                    .unwrap_or(GemEntry {
+                        version: GemVersion::default(),
+                        platform: None,
+                        revision: None,
+                    });
 
-                    let version = match capture.get(2) {
-                        Some(m) => m.as_str(),
-                        None => "0.0.0",
-                    };
-                    (
-                        name.to_string().to_lowercase(),
-                        GemVersion::from_str(version).unwrap_or_default(),
-                    )
-                }, //
-            )
-            .collect::<HashMap<String, GemVersion>>();
+                (name.to_string().to_lowercase(), entry)
+            })
+            .collect::<HashMap<String, GemEntry>>();
 
         Ok(GemList { gems })
     }
 }
 
+/// Reason a `GemList` could not be read from the `Gemfile.lock`
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub(crate) enum LockfileGemsError {
+    #[error("Gemfile not found")]
+    MissingGemfile,
+
+    #[error("{0} found")]
+    Unsupported(String),
+
+    #[error("{0} missing from Gemfile.lock")]
+    MissingSpec(String),
+}
+
+/// Locked specs for the gems `bundle install` installs
+///
+/// Starts from the `DEPENDENCIES` not excluded by `without` and follows their
+/// dependencies. See `GemList::from_lockfile`.
+///
+/// # Errors
+///
+/// Errors when the `Gemfile` uses features that cannot be evaluated statically
+/// or the `Gemfile.lock` is missing a gem.
+pub(crate) fn installed_specs<'a>(
+    gemfile_lock: &'a GemfileLock,
+    gemfile: &str,
+    without: &str,
+    platform: &str,
+) -> Result<Vec<&'a LockedSpec>, LockfileGemsError> {
+    if gemfile_lock.platforms.iter().any(|p| p == "java") {
+        return Err(LockfileGemsError::Unsupported(String::from(
+            "java platform",
+        )));
+    }
+
+    let gemfile_gems = gemfile_gems(gemfile)?;
+    let without = without
+        .split([':', ' ', ','])
+        .filter(|group| !group.is_empty())
+        .collect::<HashSet<_>>();
+    let excluded = |name: &String| {
+        gemfile_gems.other_platforms.contains(name)
+            || gemfile_gems.groups.get(name).map_or(false, |groups| {
+                !groups.is_empty() && groups.iter().all(|group| without.contains(group.as_str()))
+            })
+    };
+
+    let mut queue = gemfile_lock
+        .dependencies
+        .iter()
+        .filter(|name| !excluded(name))
+        .cloned()
+        .collect::<VecDeque<String>>();
+    let mut seen = HashSet::new();
+    let mut specs = Vec::new();
+    while let Some(name) = queue.pop_front() {
+        if name == "bundler" || !seen.insert(name.to_lowercase()) {
+            continue;
+        }
+        let spec = platform_spec(&gemfile_lock.specs, &name, platform)
+            .ok_or_else(|| LockfileGemsError::MissingSpec(name.clone()))?;
+
+        queue.extend(spec.dependencies.iter().cloned());
+        specs.push(spec);
+    }
+
+    Ok(specs)
+}
+
+/// Locked specs for the SBOM of the gems layer, before `bundle install` runs
+///
+/// Uses the same rules as `GemList::from_lockfile` with the `BUNDLE_WITHOUT` from `env`
+/// or the buildpack default. When the `Gemfile` cannot be read statically every gem
+/// in the `Gemfile.lock` is listed.
+pub(crate) fn sbom_specs<'a>(
+    app_dir: &Path,
+    gemfile_lock: &'a GemfileLock,
+    env: &Env,
+    without_default: &BundleWithout,
+) -> Vec<&'a LockedSpec> {
+    let platform = crate::steps::build_platform();
+    let without = env.get("BUNDLE_WITHOUT").map_or_else(
+        || without_default.as_str().to_string(),
+        |without| without.to_string_lossy().to_string(),
+    );
+
+    fs_err::read_to_string(app_dir.join("Gemfile"))
+        .ok()
+        .and_then(|gemfile| installed_specs(gemfile_lock, &gemfile, &without, &platform).ok())
+        .unwrap_or_else(|| all_specs(gemfile_lock, &platform))
+}
+
+/// Locked specs for every gem in the `Gemfile.lock` regardless of group
+fn all_specs<'a>(gemfile_lock: &'a GemfileLock, platform: &str) -> Vec<&'a LockedSpec> {
+    let mut seen = HashSet::new();
+    gemfile_lock
+        .specs
+        .iter()
+        .filter(|spec| seen.insert(spec.name.as_str()))
+        .filter_map(|spec| platform_spec(&gemfile_lock.specs, &spec.name, platform))
+        .collect()
+}
+
+/// Picks the variant of a gem bundler installs for the given platform
+fn platform_spec<'a>(
+    specs: &'a [LockedSpec],
+    name: &str,
+    platform: &str,
+) -> Option<&'a LockedSpec> {
+    let mut matching = specs.iter().filter(|spec| spec.name == name);
+    matching
+        .clone()
+        .find(|spec| match &spec.platform {
+            Some(spec_platform) => crate::steps::satisfies_platform(spec_platform, platform),
+            None => false,
+        })
+        .or_else(|| matching.find(|spec| spec.platform.is_none()))
+}
+
+/// Bundler platforms that install on MRI on Linux, `java` lockfiles are not read statically
+const BUILD_PLATFORMS: [&str; 2] = ["ruby", "mri"];
+
+/// Bundler platforms for other Ruby engines and operating systems
+const OTHER_PLATFORMS: [&str; 10] = [
+    "jruby",
+    "java",
+    "truffleruby",
+    "rbx",
+    "windows",
+    "mingw",
+    "x64_mingw",
+    "x64_mingw_ucrt",
+    "mswin",
+    "mswin64",
+];
+
+/// Gems declared in a `Gemfile`
+#[derive(Debug, Default)]
+struct GemfileGems {
+    /// Gem names mapped to the groups they're declared in, empty for the default group
+    groups: HashMap<String, Vec<String>>,
+
+    /// Gems only declared for other platforms i.e. `platforms: :jruby`, which bundler
+    /// does not install
+    other_platforms: HashSet<String>,
+}
+
+/// A `do` block in a `Gemfile` and the groups and platforms it applies to its gems
+struct Block {
+    groups: Vec<String>,
+    platforms: Vec<String>,
+}
+
+/// Reads the groups and platforms of the gems declared in a `Gemfile`
+///
+/// Anything beyond `source`, `ruby`, `gem`, `group`, `platforms`, and
+/// `git`/`github`/`path` blocks could change which gems are installed when evaluated,
+/// and returns an error. Versioned platforms such as `mri_31` are not supported either.
+#[allow(clippy::too_many_lines)]
+fn gemfile_gems(gemfile: &str) -> Result<GemfileGems, LockfileGemsError> {
+    let keyword_re = Regex::new(r"\A([a-z_]+)").expect("Internal error: invalid regex");
+    let name_re =
+        Regex::new(r#"\A\w+\(?\s*["']([^"']+)["']"#).expect("Internal error: invalid regex");
+    let option_re = |keys: &str| {
+        Regex::new(&format!(
+            r#"(?:\b{keys}:|:{keys}\s*=>)\s*(%[iIwW]\[[^\]]*\]|\[[^\]]*\]|:\w+|"\w+"|'\w+')?"#
+        ))
+        .expect("Internal error: invalid regex")
+    };
+    let group_option_re = option_re("groups?");
+    let platform_option_re = option_re("platforms?");
+    let percent_names_re =
+        Regex::new(r"%[iIwW]\[([^\]]*)\]").expect("Internal error: invalid regex");
+    let symbol_names_re =
+        Regex::new(r#":(\w+)|["'](\w+)["']"#).expect("Internal error: invalid regex");
+    let names = |string: &str| match percent_names_re.captures(string) {
+        Some(capture) => capture[1]
+            .split_whitespace()
+            .map(String::from)
+            .collect::<Vec<String>>(),
+        None => symbol_names_re
+            .captures_iter(string)
+            .filter_map(|capture| capture.get(1).or_else(|| capture.get(2)))
+            .map(|m| m.as_str().to_string())
+            .collect(),
+    };
+    let unsupported = |string: &str| {
+        Err(LockfileGemsError::Unsupported(format!(
+            "`{string}` in Gemfile"
+        )))
+    };
+
+    let mut blocks: Vec<Block> = Vec::new();
+    let mut gems = GemfileGems::default();
+    for line in gemfile.lines() {
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        let keyword = keyword_re
+            .captures(line)
+            .and_then(|capture| capture.get(1))
+            .map_or("", |m| m.as_str());
+        let opens_block = line.ends_with(" do");
+
+        match keyword {
+            "end" if line == "end" => {
+                blocks.pop();
+            }
+            "source" | "ruby" | "git" | "github" | "path" | "git_source"
+                if !line.contains(" do |") =>
+            {
+                if opens_block {
+                    blocks.push(Block {
+                        groups: Vec::new(),
+                        platforms: Vec::new(),
+                    });
+                }
+            }
+            "group" if opens_block && !line.contains("optional") => {
+                blocks.push(Block {
+                    groups: names(line.trim_end_matches(" do")),
+                    platforms: Vec::new(),
+                });
+            }
+            "platforms" | "platform" if opens_block => {
+                let platforms = names(line.trim_end_matches(" do"));
+                if platforms.is_empty() {
+                    return unsupported(line);
+                }
+                blocks.push(Block {
+                    groups: Vec::new(),
+                    platforms,
+                });
+            }
+            "gem"
+                if !opens_block
+                    && !["install_if", "optional"]
+                        .iter()
+                        .any(|option| line.contains(option)) =>
+            {
+                let Some(name) = name_re.captures(line).and_then(|capture| capture.get(1)) else {
+                    return unsupported(line);
+                };
+                let name = name.as_str().to_string();
+                let mut gem_groups = Vec::new();
+                let mut platforms = Vec::new();
+                for block in &blocks {
+                    gem_groups.extend(block.groups.iter().cloned());
+                    platforms.extend(block.platforms.iter().cloned());
+                }
+                if let Some(capture) = group_option_re.captures(line) {
+                    let Some(option) = capture.get(1) else {
+                        return unsupported(line);
+                    };
+                    gem_groups.extend(names(option.as_str()));
+                }
+                if let Some(capture) = platform_option_re.captures(line) {
+                    let Some(option) = capture.get(1) else {
+                        return unsupported(line);
+                    };
+                    platforms.extend(names(option.as_str()));
+                }
+
+                // Bundler installs a gem when it has no platforms or any of them match
+                match on_build_platform(&platforms) {
+                    Some(true) => {
+                        gems.other_platforms.remove(&name);
+                        gems.groups.insert(name, gem_groups);
+                    }
+                    Some(false) => {
+                        if !gems.groups.contains_key(&name) {
+                            gems.other_platforms.insert(name);
+                        }
+                    }
+                    None => return unsupported(line),
+                }
+            }
+            _ => return unsupported(line),
+        }
+    }
+
+    Ok(gems)
+}
+
+/// Whether gems limited to the given bundler platforms install on the build platform
+///
+/// `None` when a platform is not known, such as `mri_31`.
+fn on_build_platform(platforms: &[String]) -> Option<bool> {
+    if platforms.is_empty() {
+        return Some(true);
+    }
+
+    let mut matches = false;
+    for platform in platforms {
+        if BUILD_PLATFORMS.contains(&platform.as_str()) {
+            matches = true;
+        } else if !OTHER_PLATFORMS.contains(&platform.as_str()) {
+            return None;
+        }
+    }
+    Some(matches)
+}
+
+/// Removes a trailing `# comment` that is not inside a string
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    for (index, c) in line.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(open), c) if open == c => quote = None,
+            (None, '#') => return &line[..index],
+            _ => {}
+        }
+    }
+    line
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -146,5 +639,290 @@ Use `bundle info` to print more detailed information about a gem
         assert!(!gem_list.has("foo"));
 
         assert_eq!(gem_list.gems.len(), 14);
+        assert_eq!(
+            gem_list.version_for("railties").unwrap(),
+            &GemVersion::from_str("6.1.4.1").unwrap()
+        );
+        assert!(gem_list.version_for("foo").is_none());
+    }
+
+    #[test]
+    fn test_parsing_platform_and_git_entries() {
+        let gem_list = GemList::from_str(
+            r"
+Gems included by the bundle:
+  * nokogiri (1.15.4-x86_64-linux)
+  * sorbet-static (0.5.11144-universal-darwin)
+  * jruby-openssl (0.14.2-java)
+  * rails (7.1.2 abc1234)
+  * railties (7.1.2 abc1234)
+  * rack (3.0.0-rc1)
+Use `bundle info` to print more detailed information about a gem
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(gem_list.gems.len(), 6);
+        assert!(gem_list.has("railties"));
+
+        let nokogiri = gem_list.gems.get("nokogiri").unwrap();
+        assert_eq!(nokogiri.version, GemVersion::from_str("1.15.4").unwrap());
+        assert_eq!(nokogiri.platform.as_deref(), Some("x86_64-linux"));
+        assert_eq!(nokogiri.revision, None);
+        assert_eq!(nokogiri.to_string(), "1.15.4-x86_64-linux");
+
+        let sorbet = gem_list.gems.get("sorbet-static").unwrap();
+        assert_eq!(sorbet.platform.as_deref(), Some("universal-darwin"));
+
+        let openssl = gem_list.gems.get("jruby-openssl").unwrap();
+        assert_eq!(openssl.platform.as_deref(), Some("java"));
+
+        let rails = gem_list.gems.get("rails").unwrap();
+        assert_eq!(rails.version, GemVersion::from_str("7.1.2").unwrap());
+        assert_eq!(rails.platform, None);
+        assert_eq!(rails.revision.as_deref(), Some("abc1234"));
+        assert_eq!(rails.to_string(), "7.1.2 abc1234");
+
+        let rack = gem_list.gems.get("rack").unwrap();
+        assert_eq!(rack.version, GemVersion::from_str("3.0.0-rc1").unwrap());
+        assert_eq!(rack.platform, None);
+    }
+
+    fn lockfile_gems(
+        gemfile_lock: &str,
+        gemfile: &str,
+        without: &str,
+    ) -> Result<GemList, LockfileGemsError> {
+        GemList::from_lockfile(
+            &GemfileLock::from_str(gemfile_lock).unwrap(),
+            gemfile,
+            without,
+            "x86_64-linux",
+            &GemfileLock::from_str(gemfile_lock)
+                .unwrap()
+                .resolve_bundler("2.4.5"),
+        )
+    }
+
+    /// Each `bundle_list.txt` is the output of `bundle list` for the fixture app, the
+    /// integration tests check it against the built app
+    #[test]
+    fn test_from_lockfile_matches_bundle_list_on_fixtures() {
+        let fixtures = [
+            (
+                include_str!("../tests/fixtures/barnes_app/Gemfile.lock"),
+                include_str!("../tests/fixtures/barnes_app/Gemfile"),
+                include_str!("../tests/fixtures/barnes_app/bundle_list.txt"),
+            ),
+            (
+                include_str!("../tests/fixtures/default_ruby/Gemfile.lock"),
+                include_str!("../tests/fixtures/default_ruby/Gemfile"),
+                include_str!("../tests/fixtures/default_ruby/bundle_list.txt"),
+            ),
+            (
+                include_str!("../tests/fixtures/yarn-ruby-app/Gemfile.lock"),
+                include_str!("../tests/fixtures/yarn-ruby-app/Gemfile"),
+                include_str!("../tests/fixtures/yarn-ruby-app/bundle_list.txt"),
+            ),
+        ];
+
+        for (gemfile_lock, gemfile, bundle_list) in fixtures {
+            assert_eq!(
+                GemList::from_str(bundle_list).unwrap(),
+                lockfile_gems(gemfile_lock, gemfile, "development:test").unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_from_lockfile_groups_and_platforms() {
+        let gemfile_lock = r"
+GIT
+  remote: https://github.com/rails/rails.git
+  revision: abc1234def5678
+  specs:
+    railties (7.1.2)
+      rake (>= 12.2)
+
+GEM
+  remote: https://rubygems.org/
+  specs:
+    debug (1.9.0)
+      irb (>= 1.10)
+    irb (1.11.0)
+    nokogiri (1.15.4)
+      racc (~> 1.4)
+    nokogiri (1.15.4-x86_64-linux-musl)
+      racc (~> 1.4)
+    nokogiri (1.15.4-x86_64-linux)
+      racc (~> 1.4)
+    racc (1.7.3)
+    rake (13.0.6)
+    rspec (3.12.0)
+
+PLATFORMS
+  ruby
+  x86_64-linux
+  x86_64-linux-musl
+
+DEPENDENCIES
+  debug
+  nokogiri
+  railties!
+  rspec
+";
+        let gemfile = r#"
+source "https://rubygems.org"
+git_source(:github) { |repo| "https://github.com/#{repo}.git" }
+
+ruby "3.2.2"
+
+gem "railties", github: "rails/rails" # Edge
+gem "nokogiri"
+gem "debug", group: [:development, :test]
+
+group :test do
+  gem "rspec"
+end
+"#;
+
+        let gem_list = lockfile_gems(gemfile_lock, gemfile, "development:test").unwrap();
+        let mut names = gem_list.gems.keys().cloned().collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(
+            vec!["bundler", "nokogiri", "racc", "railties", "rake"],
+            names
+        );
+        let nokogiri = gem_list.gems.get("nokogiri").unwrap();
+        assert_eq!(nokogiri.platform.as_deref(), Some("x86_64-linux"));
+        let railties = gem_list.gems.get("railties").unwrap();
+        assert_eq!(railties.revision.as_deref(), Some("abc1234"));
+
+        let gem_list = lockfile_gems(gemfile_lock, gemfile, "test").unwrap();
+        assert!(gem_list.has("debug"));
+        assert!(gem_list.has("irb"));
+        assert!(!gem_list.has("rspec"));
+    }
+
+    #[test]
+    fn test_from_lockfile_rails_platforms() {
+        let gemfile_lock = r"
+GEM
+  remote: https://rubygems.org/
+  specs:
+    activerecord-jdbc-adapter (70.1-java)
+    bootsnap (1.17.0)
+      msgpack (~> 1.2)
+    debug (1.9.0)
+      irb (>= 1.10)
+    irb (1.11.0)
+    msgpack (1.7.2)
+    nio4r (2.5.9)
+    puma (6.3.0)
+      nio4r (~> 2.0)
+    tzinfo (2.0.6)
+    tzinfo-data (1.2023.3)
+      tzinfo (>= 1.0.0)
+    web-console (4.2.1)
+
+PLATFORMS
+  ruby
+  x86_64-linux
+
+DEPENDENCIES
+  activerecord-jdbc-adapter
+  bootsnap
+  debug
+  puma (>= 5.0)
+  tzinfo-data
+  web-console
+";
+        // From the default Rails 7.1 Gemfile
+        let gemfile = r#"
+source "https://rubygems.org"
+
+ruby "3.2.2"
+
+# Use the Puma web server [https://github.com/puma/puma]
+gem "puma", ">= 5.0"
+
+# Windows does not include zoneinfo files, so bundle the tzinfo-data gem
+gem "tzinfo-data", platforms: %i[ windows jruby ]
+
+# Reduces boot times through caching; required in config/boot.rb
+gem "bootsnap", require: false
+
+group :development, :test do
+  # See https://guides.rubyonrails.org/debugging_rails_applications.html#debugging-with-the-debug-gem
+  gem "debug", platforms: %i[ mri windows ]
+end
+
+group :development do
+  # Use console in development instead of irb [https://github.com/rails/web-console]
+  gem "web-console"
+end
+
+platforms :jruby do
+  gem "activerecord-jdbc-adapter"
+end
+"#;
+
+        let names = |gem_list: GemList| {
+            let mut names = gem_list.gems.into_keys().collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+        assert_eq!(
+            vec!["bootsnap", "bundler", "msgpack", "nio4r", "puma"],
+            names(lockfile_gems(gemfile_lock, gemfile, "development:test").unwrap())
+        );
+        assert_eq!(
+            vec![
+                "bootsnap",
+                "bundler",
+                "debug",
+                "irb",
+                "msgpack",
+                "nio4r",
+                "puma",
+                "web-console"
+            ],
+            names(lockfile_gems(gemfile_lock, gemfile, "").unwrap())
+        );
+    }
+
+    #[test]
+    fn test_from_lockfile_unsupported_gemfile() {
+        let gemfile_lock = include_str!("../tests/fixtures/default_ruby/Gemfile.lock");
+
+        for gemfile in [
+            "gemspec",
+            "eval_gemfile 'other.rb'",
+            "gem 'rack', platforms: :mri_31",
+            "gem 'rack', platforms: RACK_PLATFORMS",
+            "platforms :ruby_31 do\n  gem 'rack'\nend",
+            "group :docs, optional: true do\n  gem 'yard'\nend",
+            "if ENV['CI']\n  gem 'rack'\nend",
+        ] {
+            assert!(
+                matches!(
+                    lockfile_gems(gemfile_lock, gemfile, "development:test"),
+                    Err(LockfileGemsError::Unsupported(_))
+                ),
+                "{gemfile}"
+            );
+        }
+
+        assert_eq!(
+            Err(LockfileGemsError::MissingSpec(String::from("rack"))),
+            lockfile_gems("DEPENDENCIES\n  rack\n", "gem 'rack'", "")
+        );
+    }
+
+    #[test]
+    fn test_strip_comment() {
+        assert_eq!("gem 'rack' ", strip_comment("gem 'rack' # comment"));
+        assert_eq!(r#"gem "a#b""#, strip_comment(r#"gem "a#b""#));
+        assert_eq!("", strip_comment("# comment"));
     }
 }
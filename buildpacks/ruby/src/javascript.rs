@@ -0,0 +1,203 @@
+use commons::cache::{mib, CacheConfig, KeepPath, OverLimit};
+use commons::gemfile_lock::GemfileLock;
+use libcnb::data::build_plan::{BuildPlan, BuildPlanBuilder};
+use libcnb::Env;
+use std::path::{Path, PathBuf};
+
+/// Gems that compile assets with Node.js, directly or through a package manager
+const JAVASCRIPT_GEMS: [&str; 5] = [
+    "jsbundling-rails",
+    "cssbundling-rails",
+    "webpacker",
+    "shakapacker",
+    "vite_ruby",
+];
+
+/// Node.js package manager, detected from the app's lockfile
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PackageManager {
+    Npm,
+    Yarn,
+    Pnpm,
+}
+
+impl PackageManager {
+    /// Detects the package manager from the lockfile, `npm` when only `package.json` exists
+    pub(crate) fn detect(app_dir: &Path) -> Option<PackageManager> {
+        if app_dir.join("yarn.lock").exists() {
+            Some(PackageManager::Yarn)
+        } else if app_dir.join("pnpm-lock.yaml").exists() {
+            Some(PackageManager::Pnpm)
+        } else if app_dir.join("package.json").exists() {
+            Some(PackageManager::Npm)
+        } else {
+            None
+        }
+    }
+
+    /// Build plan requirement, `npm` is provided along with `node`
+    fn requires(self) -> Option<&'static str> {
+        match self {
+            PackageManager::Npm => None,
+            PackageManager::Yarn => Some("yarn"),
+            PackageManager::Pnpm => Some("pnpm"),
+        }
+    }
+}
+
+/// First locked gem that needs Node.js to compile assets
+pub(crate) fn javascript_gem(gemfile_lock: &GemfileLock) -> Option<&'static str> {
+    JAVASCRIPT_GEMS
+        .into_iter()
+        .find(|name| gemfile_lock.specs.iter().any(|spec| &spec.name == name))
+}
+
+/// Directories cached between builds for the detected JavaScript tooling
+///
+/// `node_modules` is kept at runtime and cleared when a JS lockfile changes. Over its limit
+/// it is removed whole, a partially trimmed dependency tree would break the next install.
+/// Bundler caches in `tmp/cache` are build only, compiled output in `public` is kept at runtime.
+pub(crate) fn javascript_cache(app_dir: &Path, gemfile_lock: &GemfileLock) -> Vec<CacheConfig> {
+    let has_gem = |name: &str| gemfile_lock.specs.iter().any(|spec| spec.name == name);
+    let build_only = |path: PathBuf| CacheConfig {
+        path,
+        limit: mib(100),
+        over_limit: OverLimit::RemoveOldest,
+        keep_path: KeepPath::BuildOnly,
+        invalidate_on: Vec::new(),
+    };
+    let runtime = |path: PathBuf| CacheConfig {
+        path,
+        limit: mib(100),
+        over_limit: OverLimit::RemoveOldest,
+        keep_path: KeepPath::Runtime,
+        invalidate_on: Vec::new(),
+    };
+
+    let mut caches = Vec::new();
+    if PackageManager::detect(app_dir).is_some() {
+        caches.push(CacheConfig {
+            path: app_dir.join("node_modules"),
+            limit: mib(500),
+            over_limit: OverLimit::RemoveAll,
+            keep_path: KeepPath::Runtime,
+            invalidate_on: ["yarn.lock", "package-lock.json", "pnpm-lock.yaml"]
+                .iter()
+                .map(|name| app_dir.join(name))
+                .collect(),
+        });
+    }
+    if has_gem("webpacker") || has_gem("shakapacker") {
+        caches.push(build_only(
+            app_dir.join("tmp").join("cache").join("webpacker"),
+        ));
+        caches.push(runtime(app_dir.join("public").join("packs")));
+    }
+    if has_gem("vite_ruby") {
+        caches.push(build_only(app_dir.join("tmp").join("cache").join("vite")));
+        caches.push(runtime(app_dir.join("public").join("vite")));
+    }
+    caches
+}
+
+/// Build plan for the app, requiring `node` when a gem compiles assets with JavaScript
+///
+/// Node.js is provided by a buildpack earlier in the group, such as `heroku/nodejs`. The
+/// plan falls back to requiring only `ruby`, so groups without a Node.js buildpack still
+/// pass detection when `node` is on the stack; the build fails before compiling assets
+/// if it is not. A `package.json` on its own does not require `node`, many Ruby apps ship
+/// one without using it to compile assets.
+pub(crate) fn build_plan(app_dir: &Path, gemfile_lock: Option<&GemfileLock>) -> BuildPlan {
+    let mut plan_builder = BuildPlanBuilder::new().provides("ruby");
+    if gemfile_lock.is_none() && !app_dir.join("Gemfile").exists() {
+        return plan_builder.build();
+    }
+
+    if gemfile_lock.and_then(javascript_gem).is_some() {
+        plan_builder = plan_builder.requires("ruby").requires("node");
+        if let Some(name) = PackageManager::detect(app_dir).and_then(PackageManager::requires) {
+            plan_builder = plan_builder.requires(name);
+        }
+        plan_builder = plan_builder.or().provides("ruby");
+    }
+
+    plan_builder.requires("ruby").build()
+}
+
+/// Whether a `node` executable is on the `PATH`, for example from a Node.js buildpack
+pub(crate) fn node_on_path(env: &Env) -> bool {
+    match env.get("PATH") {
+        Some(path) => std::env::split_paths(path).any(|dir| dir.join("node").is_file()),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::str::FromStr;
+
+    #[test]
+    fn test_build_plan() {
+        let tmp = tempfile::tempdir().unwrap();
+        let gemfile_lock = GemfileLock::from_str(
+            "GEM\n  remote: https://rubygems.org/\n  specs:\n    jsbundling-rails (1.2.1)\n    rack (3.0.8)\n",
+        )
+        .unwrap();
+        assert_eq!(Some("jsbundling-rails"), javascript_gem(&gemfile_lock));
+
+        let plan = toml::to_string(&build_plan(tmp.path(), Some(&gemfile_lock))).unwrap();
+        assert!(plan.contains(r#"name = "node""#));
+        let (_, fallback) = plan.split_once("[[or]]").unwrap();
+        assert!(!fallback.contains("node"));
+        assert!(fallback.contains(r#"name = "ruby""#));
+
+        fs_err::write(tmp.path().join("yarn.lock"), "").unwrap();
+        let plan = toml::to_string(&build_plan(tmp.path(), Some(&gemfile_lock))).unwrap();
+        assert!(plan.contains(r#"name = "yarn""#));
+
+        let gemfile_lock = GemfileLock::from_str(
+            "GEM\n  remote: https://rubygems.org/\n  specs:\n    rack (3.0.8)\n",
+        )
+        .unwrap();
+        let plan = toml::to_string(&build_plan(tmp.path(), Some(&gemfile_lock))).unwrap();
+        assert!(!plan.contains("node"));
+        assert!(!plan.contains("yarn"));
+
+        fs_err::remove_file(tmp.path().join("yarn.lock")).unwrap();
+        fs_err::write(tmp.path().join("package.json"), "{}").unwrap();
+        let plan = toml::to_string(&build_plan(tmp.path(), Some(&gemfile_lock))).unwrap();
+        assert!(!plan.contains("node"));
+        assert!(plan.contains(r#"name = "ruby""#));
+    }
+
+    #[test]
+    fn test_javascript_cache() {
+        let tmp = tempfile::tempdir().unwrap();
+        let gemfile_lock = GemfileLock::from_str(
+            "GEM\n  remote: https://rubygems.org/\n  specs:\n    vite_ruby (3.5.0)\n",
+        )
+        .unwrap();
+        let paths = |caches: Vec<CacheConfig>| {
+            caches
+                .into_iter()
+                .map(|cache| cache.path.strip_prefix(tmp.path()).unwrap().to_path_buf())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            vec![
+                PathBuf::from("tmp/cache/vite"),
+                PathBuf::from("public/vite")
+            ],
+            paths(javascript_cache(tmp.path(), &gemfile_lock))
+        );
+
+        fs_err::write(tmp.path().join("package.json"), "{}").unwrap();
+        let caches = javascript_cache(tmp.path(), &gemfile_lock);
+        assert_eq!(tmp.path().join("node_modules"), caches[0].path);
+        assert!(caches[0]
+            .invalidate_on
+            .contains(&tmp.path().join("yarn.lock")));
+    }
+}
@@ -1,4 +1,6 @@
 pub(crate) mod bundle_download_layer;
 pub(crate) mod bundle_install_layer;
+pub(crate) mod gem_cache_layer;
 pub(crate) mod metrics_agent_install;
+pub(crate) mod rake_detect_layer;
 pub(crate) mod ruby_install_layer;
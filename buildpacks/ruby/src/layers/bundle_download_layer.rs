@@ -3,6 +3,7 @@ use commons::output::{
     section_log::{log_step, log_step_timed, SectionLogger},
 };
 
+use crate::bundler_credentials::Redact;
 use crate::RubyBuildpack;
 use crate::RubyBuildpackError;
 use commons::gemfile_lock::ResolvedBundlerVersion;
@@ -82,9 +83,13 @@ impl<'a> Layer for BundleDownloadLayer<'a> {
                 fun_run::map_which_problem(error, cmd.mut_cmd(), self.env.get("PATH").cloned())
             })
         })
-        .map_err(RubyBuildpackError::GemInstallBundlerCommandError)?;
+        .map_err(|error| {
+            let redact = Redact::from_env(&self.env);
+            RubyBuildpackError::GemInstallBundlerCommandError(redact.cmd_error(error), redact)
+        })?;
 
         LayerResultBuilder::new(self.metadata.clone())
+            .sbom(crate::sbom::bundler(&self.metadata.version))
             .env(
                 LayerEnv::new()
                     .chainable_insert(Scope::All, ModificationBehavior::Delimiter, "PATH", ":")
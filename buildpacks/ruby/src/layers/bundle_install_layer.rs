@@ -3,21 +3,28 @@ use commons::output::{
     section_log::{log_step, log_step_stream, SectionLogger},
 };
 
+use crate::bundler_credentials::Redact;
+use crate::command_timeout::{self, TimedCommand};
 use crate::{BundleWithout, RubyBuildpack, RubyBuildpackError};
 use commons::{
-    display::SentenceList, gemfile_lock::ResolvedRubyVersion, metadata_digest::MetadataDigest,
+    display::SentenceList,
+    gemfile_lock::{GemfileLock, ResolvedRubyVersion},
+    metadata_digest::MetadataDigest,
 };
 use fun_run::CommandWithName;
-use fun_run::{self, CmdError};
 use libcnb::{
     build::BuildContext,
     data::{buildpack::StackId, layer_content_metadata::LayerTypes},
     layer::{ExistingLayerStrategy, Layer, LayerData, LayerResult, LayerResultBuilder},
     layer_env::{LayerEnv, ModificationBehavior, Scope},
+    sbom::Sbom,
     Env,
 };
 use serde::{Deserialize, Serialize};
-use std::{path::Path, process::Command};
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
 
 const HEROKU_SKIP_BUNDLE_DIGEST: &str = "HEROKU_SKIP_BUNDLE_DIGEST";
 pub(crate) const FORCE_BUNDLE_INSTALL_CACHE_KEY: &str = "v1";
@@ -29,12 +36,20 @@ pub(crate) const FORCE_BUNDLE_INSTALL_CACHE_KEY: &str = "v1";
 ///
 /// To help achieve this the logic inside of `BundleInstallLayer::update` and
 /// `BundleInstallLayer::create` are the same.
+///
+/// After install, files that are not needed to run the application are pruned from
+/// the layer. Downloaded `.gem` files are moved to the `gem_cache_path`
+/// (see `GemCacheLayer`) so they're available to the next build.
 #[derive(Debug)]
 pub(crate) struct BundleInstallLayer<'a> {
     pub env: Env,
     pub without: BundleWithout,
+    pub gem_cache_path: PathBuf,
     pub _section_log: &'a dyn SectionLogger,
     pub metadata: BundleInstallLayerMetadata,
+
+    /// Describes the installed gems, see `crate::sbom::gems`
+    pub sbom: Sbom,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
@@ -68,6 +83,41 @@ impl<'a> BundleInstallLayer<'a> {
 
         Ok(out)
     }
+
+    /// Move cached `.gem` files into the layer so `bundle install` and `bundle pristine`
+    /// do not need to download them again
+    fn load_gem_files(&self, layer_path: &Path) -> Result<(), RubyBuildpackError> {
+        let gem_dir = gem_dir(layer_path, &self.metadata.ruby_version);
+
+        move_gem_files(&self.gem_cache_path, &gem_dir.join("cache"))
+            .map_err(RubyBuildpackError::GemsLayerPruneError)?;
+
+        Ok(())
+    }
+
+    /// Move `.gem` files back to the cache and remove build artifacts
+    /// so they do not ship with the launch image
+    fn prune(&self, layer_path: &Path) -> Result<(), RubyBuildpackError> {
+        let gem_dir = gem_dir(layer_path, &self.metadata.ruby_version);
+
+        clear_gem_files(&self.gem_cache_path).map_err(RubyBuildpackError::GemsLayerPruneError)?;
+        let moved = move_gem_files(&gem_dir.join("cache"), &self.gem_cache_path)
+            .map_err(RubyBuildpackError::GemsLayerPruneError)?;
+        let pruned = prune_gem_dir(&gem_dir).map_err(RubyBuildpackError::GemsLayerPruneError)?;
+
+        log_step(format!(
+            "Pruned gems {}",
+            fmt::details(format!(
+                "{} reclaimed, moved {moved_count} {gem} file(s) to the build cache, removed {pruned_count} build artifact(s)",
+                display_bytes(moved.bytes + pruned.bytes),
+                moved_count = moved.count,
+                pruned_count = pruned.count,
+                gem = fmt::value(".gem"),
+            ))
+        ));
+
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
@@ -124,11 +174,14 @@ impl Layer for BundleInstallLayer<'_> {
         let layer_env = self.build_layer_env(context, &layer_data.path)?;
         let env = layer_env.apply(Scope::Build, &self.env);
 
-        match update_state(&layer_data.content_metadata.metadata, &metadata) {
+        self.load_gem_files(&layer_data.path)?;
+
+        let old = &layer_data.content_metadata.metadata;
+        match update_state(old, &metadata) {
             UpdateState::Run(reason) => {
                 log_step(reason);
 
-                bundle_install(&env).map_err(RubyBuildpackError::BundleInstallCommandError)?;
+                bundle_install(&env)?;
             }
             UpdateState::Skip(checked) => {
                 let bundle_install = fmt::value("bundle install");
@@ -145,7 +198,25 @@ impl Layer for BundleInstallLayer<'_> {
             }
         }
 
-        LayerResultBuilder::new(metadata).env(layer_env).build()
+        // The ABI is unchanged (otherwise the layer is recreated) but extensions
+        // were compiled against the prior Ruby patch release
+        if old.ruby_version != metadata.ruby_version {
+            let gems = native_extension_gems(&layer_data.path, &metadata.ruby_version);
+            if gems.is_empty() {
+                log_step(format!(
+                    "Skipping {} (no gems with native extensions)",
+                    fmt::value("bundle pristine")
+                ));
+            } else {
+                bundle_pristine(&env, &gems)?;
+            }
+        }
+        self.prune(&layer_data.path)?;
+
+        LayerResultBuilder::new(metadata)
+            .env(layer_env)
+            .sbom(self.sbom.clone())
+            .build()
     }
 
     /// Runs when with empty cache
@@ -157,10 +228,13 @@ impl Layer for BundleInstallLayer<'_> {
         let layer_env = self.build_layer_env(context, layer_path)?;
         let env = layer_env.apply(Scope::Build, &self.env);
 
-        bundle_install(&env).map_err(RubyBuildpackError::BundleInstallCommandError)?;
+        self.load_gem_files(layer_path)?;
+        bundle_install(&env)?;
+        self.prune(layer_path)?;
 
         LayerResultBuilder::new(self.metadata.clone())
             .env(layer_env)
+            .sbom(self.sbom.clone())
             .build()
     }
 
@@ -193,14 +267,22 @@ impl Layer for BundleInstallLayer<'_> {
 
                 clear_and_run
             }
-            Changed::RubyVersion(_old, _now) => {
+            Changed::RubyAbi(_old, _now) => {
                 log_step(format!(
                     "Clearing cache {}",
-                    fmt::details("ruby version changed")
+                    fmt::details("ruby engine or ABI version changed")
                 ));
 
                 clear_and_run
             }
+            Changed::RubyPatch(_old, _now) => {
+                log_step(format!(
+                    "Loading cache {}",
+                    fmt::details("ruby version changed, ABI version unchanged")
+                ));
+
+                keep_and_run
+            }
         }
     }
 }
@@ -217,11 +299,15 @@ enum Changed {
     /// TODO: Only clear native dependencies instead of the whole cache
     Stack(StackId, StackId), // (old, now)
 
-    /// Ruby version changed i.e. 3.0.2 to 3.1.2
-    /// When that happens we must invalidate native dependency gems
-    /// because they're linked to a specific compiled version of Ruby.
-    /// TODO: Only clear native dependencies instead of the whole cache
-    RubyVersion(ResolvedRubyVersion, ResolvedRubyVersion), // (old, now)
+    /// Ruby ABI version or engine changed i.e. 3.1.2 (3.1.0) to 3.2.2 (3.2.0)
+    /// When that happens we must invalidate all gems because they're
+    /// installed to a directory specific to the ABI version.
+    RubyAbi(ResolvedRubyVersion, ResolvedRubyVersion), // (old, now)
+
+    /// Ruby version changed within the same ABI i.e. 3.2.2 to 3.2.3
+    /// Pure Ruby gems can be reused, but native extensions are linked against
+    /// a specific compiled version of Ruby and must be rebuilt.
+    RubyPatch(ResolvedRubyVersion, ResolvedRubyVersion), // (old, now)
 }
 
 // Compare the old metadata to current metadata to determine the state of the
@@ -236,13 +322,259 @@ fn cache_state(old: BundleInstallLayerMetadata, now: BundleInstallLayerMetadata)
 
     if old.stack != stack {
         Changed::Stack(old.stack, stack)
+    } else if old.ruby_version.abi_key() != ruby_version.abi_key() {
+        Changed::RubyAbi(old.ruby_version, ruby_version)
     } else if old.ruby_version != ruby_version {
-        Changed::RubyVersion(old.ruby_version, ruby_version)
+        Changed::RubyPatch(old.ruby_version, ruby_version)
     } else {
         Changed::Nothing
     }
 }
 
+/// Names of installed gems that compiled a native extension
+///
+/// Rubygems places compiled extensions in
+/// `<layer>/<engine>/<abi>/extensions/<platform>/<abi>/<name>-<version>`.
+fn native_extension_gems(layer_path: &Path, ruby_version: &ResolvedRubyVersion) -> Vec<String> {
+    let pattern = gem_dir(layer_path, ruby_version).join("extensions/*/*/*");
+    let mut gems = glob::glob(&pattern.to_string_lossy())
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .filter(|path| path.is_dir())
+        .filter_map(|path| {
+            path.file_name()
+                .map(|name| name.to_string_lossy().to_string())
+        })
+        .filter_map(|full_name| gem_name_from_full_name(&full_name))
+        .collect::<Vec<String>>();
+    gems.sort();
+    gems.dedup();
+
+    gems
+}
+
+/// Strip the version (and platform) from a gem's full name i.e. `nokogiri-1.15.4`
+fn gem_name_from_full_name(full_name: &str) -> Option<String> {
+    let mut parts = full_name.split('-').collect::<Vec<_>>();
+    let version_index = parts
+        .iter()
+        .rposition(|part| part.starts_with(|c: char| c.is_ascii_digit()))?;
+    parts.truncate(version_index);
+
+    (!parts.is_empty()).then(|| parts.join("-"))
+}
+
+/// Where rubygems installs gems inside of the layer i.e. `<layer>/ruby/3.2.0`
+fn gem_dir(layer_path: &Path, ruby_version: &ResolvedRubyVersion) -> PathBuf {
+    layer_path
+        .join(ruby_version.engine())
+        .join(ruby_version.abi_version())
+}
+
+/// Number and size of files that were moved or removed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct FileCount {
+    count: usize,
+    bytes: u64,
+}
+
+impl FileCount {
+    fn add(&mut self, path: &Path) -> std::io::Result<()> {
+        self.count += 1;
+        self.bytes += dir_size(path)?;
+        Ok(())
+    }
+}
+
+fn dir_size(path: &Path) -> std::io::Result<u64> {
+    walkdir::WalkDir::new(path)
+        .into_iter()
+        .map(|entry| {
+            let entry = entry?;
+            Ok(if entry.file_type().is_file() {
+                entry.metadata()?.len()
+            } else {
+                0
+            })
+        })
+        .sum()
+}
+
+fn display_bytes(bytes: u64) -> String {
+    byte_unit::Byte::from_bytes(u128::from(bytes))
+        .get_appropriate_unit(true)
+        .to_string()
+}
+
+fn gem_files(dir: &Path) -> Vec<PathBuf> {
+    glob::glob(&dir.join("*.gem").to_string_lossy())
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .collect()
+}
+
+/// Move `*.gem` files between directories, files already in `to` are kept
+fn move_gem_files(from: &Path, to: &Path) -> std::io::Result<FileCount> {
+    let mut moved = FileCount::default();
+    fs_err::create_dir_all(to)?;
+
+    for path in gem_files(from) {
+        let Some(name) = path.file_name() else {
+            continue;
+        };
+        let destination = to.join(name);
+        if destination.exists() {
+            continue;
+        }
+
+        moved.add(&path)?;
+        if fs_err::rename(&path, &destination).is_err() {
+            fs_err::copy(&path, &destination)?;
+            fs_err::remove_file(&path)?;
+        }
+    }
+
+    Ok(moved)
+}
+
+/// Remove prior `.gem` files, the current set is moved in after install so
+/// gems removed by `bundle clean` do not accumulate
+fn clear_gem_files(dir: &Path) -> std::io::Result<()> {
+    for path in gem_files(dir) {
+        fs_err::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// Remove files from installed gems that are not needed at runtime
+///
+/// - Native extension build artifacts in `gems/*/ext` (`*.o`, `Makefile`, `mkmf.log`),
+///   the compiled result lives in `extensions/` or `lib/`
+/// - Native extension build logs in `extensions/` (`mkmf.log`)
+/// - Test suites shipped with gems in `gems/*/test` and `gems/*/spec`
+fn prune_gem_dir(gem_dir: &Path) -> std::io::Result<FileCount> {
+    let mut pruned = FileCount::default();
+    let gems = match fs_err::read_dir(gem_dir.join("gems")) {
+        Ok(entries) => entries.filter_map(Result::ok).map(|entry| entry.path()),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(pruned),
+        Err(error) => return Err(error),
+    }
+    .filter(|path| path.is_dir())
+    .collect::<Vec<PathBuf>>();
+
+    for gem in &gems {
+        for name in ["test", "spec"] {
+            let path = gem.join(name);
+            if path.is_dir() {
+                pruned.add(&path)?;
+                fs_err::remove_dir_all(&path)?;
+            }
+        }
+    }
+
+    let artifacts = gems
+        .iter()
+        .map(|gem| gem.join("ext"))
+        .chain([gem_dir.join("extensions")])
+        .filter(|path| path.is_dir())
+        .flat_map(|dir| {
+            walkdir::WalkDir::new(dir)
+                .into_iter()
+                .filter_map(Result::ok)
+        })
+        .filter(|entry| entry.file_type().is_file())
+        .map(walkdir::DirEntry::into_path)
+        .filter(|path| is_build_artifact(path))
+        .collect::<Vec<PathBuf>>();
+
+    for path in artifacts {
+        pruned.add(&path)?;
+        fs_err::remove_file(&path)?;
+    }
+
+    Ok(pruned)
+}
+
+fn is_build_artifact(path: &Path) -> bool {
+    let in_ext_dir = path
+        .components()
+        .any(|component| component.as_os_str() == "ext");
+    let name = path.file_name().unwrap_or_default();
+
+    name == "mkmf.log"
+        || (in_ext_dir && (name == "Makefile" || path.extension().map_or(false, |ext| ext == "o")))
+}
+
+/// Rebuild native extensions of already installed gems, the results are streamed to stdout/stderr.
+///
+/// # Errors
+///
+/// When the 'bundle pristine' command fails or times out this function returns an error.
+///
+fn bundle_pristine(env: &Env, gems: &[String]) -> Result<(), RubyBuildpackError> {
+    let timeout = TimedCommand::BundleInstall.timeout(env)?;
+    let path_env = env.get("PATH").cloned();
+    let redact = Redact::from_env(env);
+
+    let mut cmd = Command::new("bundle");
+    cmd.env_clear() // Current process env vars already merged into env
+        .arg("pristine")
+        .args(gems)
+        .envs(env);
+
+    log_step_stream(format!("Running {}", fmt::command(cmd.name())), |stream| {
+        command_timeout::stream_output(
+            &mut cmd,
+            redact.writer(stream.io()),
+            redact.writer(stream.io()),
+            timeout,
+        )
+    })
+    .map_err(|error| {
+        error.map_cmd(|error| fun_run::map_which_problem(error, cmd.mut_cmd(), path_env))
+    })
+    .map_err(|error| error.redact(&redact))
+    .map_err(|error| error.into_buildpack_error(RubyBuildpackError::BundleInstallCommandError))?;
+
+    Ok(())
+}
+
+/// Files and directories that can change the result of `bundle install`
+///
+/// Along with the `Gemfile` and `Gemfile.lock` this includes gems loaded from a local
+/// `path:` (such as engines in a monorepo) so changes to their gemspec or sources
+/// trigger a re-install. When a path source contains the application itself (i.e. a
+/// `gemspec` directive) only its `*.gemspec` files are tracked, otherwise any change
+/// to the app would invalidate the digest.
+pub(crate) fn digest_paths(app_dir: &Path, gemfile_lock: &GemfileLock) -> Vec<PathBuf> {
+    let mut paths = vec![app_dir.join("Gemfile"), app_dir.join("Gemfile.lock")];
+    let app_dir_real = fs_err::canonicalize(app_dir).unwrap_or_else(|_| app_dir.to_path_buf());
+
+    for source in &gemfile_lock.path_sources {
+        let Ok(dir) = fs_err::canonicalize(app_dir.join(&source.remote)) else {
+            continue; // Missing directories are reported by `bundle install`
+        };
+
+        if app_dir_real.starts_with(&dir) {
+            let pattern = dir.join("*.gemspec");
+            paths.extend(
+                glob::glob(&pattern.to_string_lossy())
+                    .into_iter()
+                    .flatten()
+                    .filter_map(Result::ok),
+            );
+        } else if dir.is_dir() {
+            paths.push(dir);
+        }
+    }
+    paths.sort();
+    paths.dedup();
+
+    paths
+}
+
 fn layer_env(layer_path: &Path, app_dir: &Path, without_default: &BundleWithout) -> LayerEnv {
     // CAREFUL: See environment variable warning below vvvvvvvvvv
     let layer_env = LayerEnv::new()
@@ -304,8 +636,12 @@ fn layer_env(layer_path: &Path, app_dir: &Path, without_default: &BundleWithout)
 ///
 /// When the 'bundle install' command fails this function returns an error.
 ///
-fn bundle_install(env: &Env) -> Result<(), CmdError> {
+fn bundle_install(env: &Env) -> Result<(), RubyBuildpackError> {
+    let timeout = TimedCommand::BundleInstall.timeout(env)?;
     let path_env = env.get("PATH").cloned();
+    let redact = Redact::from_env(env);
+
+    // CAREFUL: Never display credentials, see `bundler_credentials::is_credential_key`
     let display_with_env = |cmd: &'_ mut Command| {
         fun_run::display_with_env_keys(
             cmd,
@@ -330,9 +666,18 @@ fn bundle_install(env: &Env) -> Result<(), CmdError> {
     let mut cmd = cmd.named_fn(display_with_env);
 
     log_step_stream(format!("Running {}", fmt::command(cmd.name())), |stream| {
-        cmd.stream_output(stream.io(), stream.io())
+        command_timeout::stream_output(
+            &mut cmd,
+            redact.writer(stream.io()),
+            redact.writer(stream.io()),
+            timeout,
+        )
+    })
+    .map_err(|error| {
+        error.map_cmd(|error| fun_run::map_which_problem(error, cmd.mut_cmd(), path_env))
     })
-    .map_err(|error| fun_run::map_which_problem(error, cmd.mut_cmd(), path_env))?;
+    .map_err(|error| error.redact(&redact))
+    .map_err(|error| error.into_buildpack_error(RubyBuildpackError::BundleInstallCommandError))?;
 
     Ok(())
 }
@@ -400,6 +745,168 @@ GEM_PATH=layer_path
         assert_eq!(expected.trim(), actual.trim());
     }
 
+    fn metadata(ruby_version: &str) -> BundleInstallLayerMetadata {
+        BundleInstallLayerMetadata {
+            stack: stack_id!("heroku-22"),
+            ruby_version: ResolvedRubyVersion(String::from(ruby_version)),
+            force_bundle_install_key: String::from("v1"),
+            digest: MetadataDigest::default(),
+        }
+    }
+
+    #[test]
+    fn cache_state_ruby_abi() {
+        assert!(matches!(
+            cache_state(metadata("3.2.2"), metadata("3.2.2")),
+            Changed::Nothing
+        ));
+        assert!(matches!(
+            cache_state(metadata("3.2.2"), metadata("3.2.3")),
+            Changed::RubyPatch(_, _)
+        ));
+        assert!(matches!(
+            cache_state(metadata("3.1.4"), metadata("3.2.3")),
+            Changed::RubyAbi(_, _)
+        ));
+        assert!(matches!(
+            cache_state(metadata("2.6.8"), metadata("2.6.8-jruby-9.3.6.0")),
+            Changed::RubyAbi(_, _)
+        ));
+    }
+
+    #[test]
+    fn test_native_extension_gems() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let extensions = tmpdir
+            .path()
+            .join("ruby/3.2.0/extensions/x86_64-linux/3.2.0");
+        for dir in [
+            "nokogiri-1.15.4",
+            "nio4r-2.5.9",
+            "sorbet-static-0.5.1-x86_64-linux",
+        ] {
+            fs_err::create_dir_all(extensions.join(dir)).unwrap();
+        }
+        fs_err::write(extensions.join("gem.build_complete"), "").unwrap();
+
+        assert_eq!(
+            native_extension_gems(tmpdir.path(), &ResolvedRubyVersion(String::from("3.2.3"))),
+            vec![
+                String::from("nio4r"),
+                String::from("nokogiri"),
+                String::from("sorbet-static")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_prune_gem_dir() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let gem_dir = tmpdir.path().join("ruby").join("3.2.0");
+        let nokogiri = gem_dir.join("gems").join("nokogiri-1.15.4");
+        let files = [
+            nokogiri.join("lib/nokogiri.rb"),
+            nokogiri.join("lib/nokogiri/nokogiri.so"),
+            nokogiri.join("ext/nokogiri/extconf.rb"),
+            nokogiri.join("ext/nokogiri/xml_node.o"),
+            nokogiri.join("ext/nokogiri/Makefile"),
+            nokogiri.join("ext/nokogiri/mkmf.log"),
+            nokogiri.join("test/test_nokogiri.rb"),
+            nokogiri.join("spec/nokogiri_spec.rb"),
+            gem_dir.join("extensions/x86_64-linux/3.2.0/nokogiri-1.15.4/mkmf.log"),
+            gem_dir.join("extensions/x86_64-linux/3.2.0/nokogiri-1.15.4/nokogiri/nokogiri.so"),
+        ];
+        for file in &files {
+            fs_err::create_dir_all(file.parent().unwrap()).unwrap();
+            fs_err::write(file, "1234").unwrap();
+        }
+
+        let pruned = prune_gem_dir(&gem_dir).unwrap();
+        assert_eq!(
+            pruned,
+            FileCount {
+                count: 6,
+                bytes: 24
+            }
+        );
+
+        let remaining = files
+            .iter()
+            .filter(|file| file.exists())
+            .map(|file| file.strip_prefix(&gem_dir).unwrap().to_path_buf())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            remaining,
+            vec![
+                PathBuf::from("gems/nokogiri-1.15.4/lib/nokogiri.rb"),
+                PathBuf::from("gems/nokogiri-1.15.4/lib/nokogiri/nokogiri.so"),
+                PathBuf::from("gems/nokogiri-1.15.4/ext/nokogiri/extconf.rb"),
+                PathBuf::from("extensions/x86_64-linux/3.2.0/nokogiri-1.15.4/nokogiri/nokogiri.so"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_move_gem_files() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let cache = tmpdir.path().join("gem_cache");
+        let gem_dir_cache = tmpdir.path().join("gems").join("cache");
+        fs_err::create_dir_all(&gem_dir_cache).unwrap();
+        fs_err::write(gem_dir_cache.join("rack-3.0.2.gem"), "rack").unwrap();
+        fs_err::write(gem_dir_cache.join("README"), "not a gem").unwrap();
+
+        let moved = move_gem_files(&gem_dir_cache, &cache).unwrap();
+        assert_eq!(moved, FileCount { count: 1, bytes: 4 });
+        assert!(cache.join("rack-3.0.2.gem").exists());
+        assert!(!gem_dir_cache.join("rack-3.0.2.gem").exists());
+        assert!(gem_dir_cache.join("README").exists());
+
+        clear_gem_files(&cache).unwrap();
+        assert!(gem_files(&cache).is_empty());
+    }
+
+    #[test]
+    fn digest_paths_include_path_gems() {
+        use core::str::FromStr;
+
+        let tmpdir = tempfile::tempdir().unwrap();
+        let app_dir = fs_err::canonicalize(tmpdir.path()).unwrap();
+        let engine = app_dir.join("engines").join("billing");
+        fs_err::create_dir_all(&engine).unwrap();
+        fs_err::write(app_dir.join("my_app.gemspec"), "").unwrap();
+        fs_err::write(engine.join("billing.gemspec"), "").unwrap();
+
+        let gemfile_lock = GemfileLock::from_str(
+            r"
+PATH
+  remote: .
+  specs:
+    my_app (0.1.0)
+
+PATH
+  remote: engines/billing
+  specs:
+    billing (1.2.0)
+
+PATH
+  remote: engines/missing
+  specs:
+    missing (0.0.1)
+",
+        )
+        .unwrap();
+
+        assert_eq!(
+            digest_paths(&app_dir, &gemfile_lock),
+            vec![
+                app_dir.join("Gemfile"),
+                app_dir.join("Gemfile.lock"),
+                engine,
+                app_dir.join("my_app.gemspec"),
+            ]
+        );
+    }
+
     /// If this test fails due to a change you'll need to implement
     /// `migrate_incompatible_metadata` for the Layer trait
     #[test]
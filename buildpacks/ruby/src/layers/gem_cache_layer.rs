@@ -0,0 +1,48 @@
+use crate::{RubyBuildpack, RubyBuildpackError};
+use libcnb::build::BuildContext;
+use libcnb::data::layer_content_metadata::LayerTypes;
+use libcnb::layer::{ExistingLayerStrategy, Layer, LayerData, LayerResult, LayerResultBuilder};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// # Cache `.gem` files between builds
+///
+/// Rubygems keeps a copy of every downloaded gem in `<gem-dir>/cache/*.gem`. They're not
+/// needed to run the application, but they're used by `bundle pristine` and save
+/// re-downloading gems.
+///
+/// The `BundleInstallLayer` loads these files before running `bundle install` and moves
+/// them back afterwards so they do not ship in the launch image.
+pub(crate) struct GemCacheLayer;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub(crate) struct GemCacheLayerMetadata {}
+
+impl Layer for GemCacheLayer {
+    type Buildpack = RubyBuildpack;
+    type Metadata = GemCacheLayerMetadata;
+
+    fn types(&self) -> LayerTypes {
+        LayerTypes {
+            build: false,
+            launch: false,
+            cache: true,
+        }
+    }
+
+    fn create(
+        &self,
+        _context: &BuildContext<Self::Buildpack>,
+        _layer_path: &Path,
+    ) -> Result<LayerResult<Self::Metadata>, RubyBuildpackError> {
+        LayerResultBuilder::new(GemCacheLayerMetadata::default()).build()
+    }
+
+    fn existing_layer_strategy(
+        &self,
+        _context: &BuildContext<Self::Buildpack>,
+        _layer_data: &LayerData<Self::Metadata>,
+    ) -> Result<ExistingLayerStrategy, RubyBuildpackError> {
+        Ok(ExistingLayerStrategy::Keep)
+    }
+}
@@ -0,0 +1,160 @@
+use commons::output::{
+    fmt::{self, HELP},
+    section_log::{log_step, SectionLogger},
+};
+
+use crate::bundler_credentials::Redact;
+use crate::command_timeout::Timeout;
+use crate::rake_task_detect::RakeDetect;
+use crate::{RubyBuildpack, RubyBuildpackError};
+use commons::{display::SentenceList, metadata_digest::MetadataDigest};
+use libcnb::{
+    build::BuildContext,
+    data::layer_content_metadata::LayerTypes,
+    layer::{ExistingLayerStrategy, Layer, LayerData, LayerResult, LayerResultBuilder},
+    Env,
+};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+pub(crate) const HEROKU_SKIP_RAKE_DIGEST: &str = "HEROKU_SKIP_RAKE_DIGEST";
+
+/// # Cache `rake -P` results between builds
+///
+/// Listing rake tasks loads the whole application, which is slow for large apps. The
+/// parsed tasks are stored in the layer metadata along with a digest of the files that
+/// define them. When the digest is unchanged the cached tasks are used instead of
+/// running `rake -P` again. Set `HEROKU_SKIP_RAKE_DIGEST` to bypass the cache.
+pub(crate) struct RakeDetectLayer<'a> {
+    pub env: &'a Env,
+    pub timeout: Timeout,
+    pub digest: MetadataDigest,
+    pub section_log: &'a dyn SectionLogger,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct RakeDetectLayerMetadata {
+    pub rake_detect: RakeDetect,
+    pub digest: MetadataDigest, // Must be last for serde to be happy https://github.com/toml-rs/toml-rs/issues/142
+}
+
+/// Files that change which rake tasks an application defines
+///
+/// The `Gemfile.lock`, the `Rakefile`, and `lib/tasks` and `config` when they exist.
+/// Rails apps can define or remove tasks from initializers and `config/application.rb`.
+/// Tasks defined elsewhere, i.e. from `app/`, need `HEROKU_SKIP_RAKE_DIGEST`.
+pub(crate) fn digest_paths(app_dir: &Path, rakefile: &Path) -> Vec<PathBuf> {
+    let dirs = [app_dir.join("lib").join("tasks"), app_dir.join("config")];
+
+    [app_dir.join("Gemfile.lock"), rakefile.to_path_buf()]
+        .into_iter()
+        .chain(dirs.into_iter().filter(|dir| dir.is_dir()))
+        .collect()
+}
+
+/// Runs `rake -P`, failing the build when it errors
+///
+/// # Errors
+///
+/// Errors if `rake -P` fails or does not finish within the timeout.
+pub(crate) fn rake_detect(
+    logger: &dyn SectionLogger,
+    env: &Env,
+    timeout: Timeout,
+) -> Result<RakeDetect, RubyBuildpackError> {
+    RakeDetect::from_rake_command(logger, env, true, timeout)
+        .map_err(|error| error.redact(&Redact::from_env(env)))
+        .map_err(|error| error.into_buildpack_error(RubyBuildpackError::RakeDetectError))
+}
+
+impl Layer for RakeDetectLayer<'_> {
+    type Buildpack = RubyBuildpack;
+    type Metadata = RakeDetectLayerMetadata;
+
+    fn types(&self) -> LayerTypes {
+        LayerTypes {
+            build: false,
+            launch: false,
+            cache: true,
+        }
+    }
+
+    fn create(
+        &self,
+        _context: &BuildContext<Self::Buildpack>,
+        _layer_path: &Path,
+    ) -> Result<LayerResult<Self::Metadata>, RubyBuildpackError> {
+        LayerResultBuilder::new(RakeDetectLayerMetadata {
+            rake_detect: rake_detect(self.section_log, self.env, self.timeout)?,
+            digest: self.digest.clone(),
+        })
+        .build()
+    }
+
+    fn existing_layer_strategy(
+        &self,
+        _context: &BuildContext<Self::Buildpack>,
+        layer_data: &LayerData<Self::Metadata>,
+    ) -> Result<ExistingLayerStrategy, RubyBuildpackError> {
+        let old = &layer_data.content_metadata.metadata;
+        let rake_p = fmt::value("rake -P");
+
+        if let Some(changed) = self.digest.changed(&old.digest) {
+            log_step(format!(
+                "Clearing rake task cache {}",
+                fmt::details(changed.to_string())
+            ));
+
+            Ok(ExistingLayerStrategy::Recreate)
+        } else {
+            log_step(format!(
+                "Using cached rake tasks (no changes found in {sources})",
+                sources = SentenceList::new(&self.digest.checked_list()).join_str("or")
+            ));
+            log_step(format!(
+                "{HELP} To force run {rake_p} set {}",
+                fmt::value(format!("{HEROKU_SKIP_RAKE_DIGEST}=1"))
+            ));
+
+            Ok(ExistingLayerStrategy::Keep)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_digest_paths() {
+        let tmp = tempfile::tempdir().unwrap();
+        let app_dir = tmp.path();
+        let rakefile = app_dir.join("Rakefile");
+
+        assert_eq!(
+            vec![app_dir.join("Gemfile.lock"), rakefile.clone()],
+            digest_paths(app_dir, &rakefile)
+        );
+
+        fs_err::create_dir_all(app_dir.join("lib").join("tasks")).unwrap();
+        assert_eq!(
+            vec![
+                app_dir.join("Gemfile.lock"),
+                rakefile.clone(),
+                app_dir.join("lib").join("tasks")
+            ],
+            digest_paths(app_dir, &rakefile)
+        );
+
+        fs_err::create_dir_all(app_dir.join("config")).unwrap();
+        assert_eq!(
+            vec![
+                app_dir.join("Gemfile.lock"),
+                rakefile.clone(),
+                app_dir.join("lib").join("tasks"),
+                app_dir.join("config")
+            ],
+            digest_paths(app_dir, &rakefile)
+        );
+    }
+}
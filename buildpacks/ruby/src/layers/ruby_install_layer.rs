@@ -71,7 +71,9 @@ impl<'a> Layer for RubyInstallLayer<'a> {
 
             untar(tmp_ruby_tgz.path(), layer_path).map_err(RubyBuildpackError::RubyInstallError)?;
 
-            LayerResultBuilder::new(self.metadata.clone()).build()
+            LayerResultBuilder::new(self.metadata.clone())
+                .sbom(crate::sbom::ruby(&self.metadata.version))
+                .build()
         })
     }
 
@@ -6,17 +6,18 @@ use commons::gemfile_lock::GemfileLock;
 use commons::metadata_digest::MetadataDigest;
 use commons::output::warn_later::WarnGuard;
 #[allow(clippy::wildcard_imports)]
-use commons::output::{build_log::*, fmt};
+use commons::output::{build_log::*, fmt, section_log::log_step};
 use core::str::FromStr;
+use framework::{AssetTask, Framework};
 use fun_run::CmdError;
 use layers::{
     bundle_download_layer::{BundleDownloadLayer, BundleDownloadLayerMetadata},
     bundle_install_layer::{BundleInstallLayer, BundleInstallLayerMetadata},
+    gem_cache_layer::GemCacheLayer,
     metrics_agent_install::{MetricsAgentInstall, MetricsAgentInstallError},
     ruby_install_layer::{RubyInstallError, RubyInstallLayer, RubyInstallLayerMetadata},
 };
 use libcnb::build::{BuildContext, BuildResult, BuildResultBuilder};
-use libcnb::data::build_plan::BuildPlanBuilder;
 use libcnb::data::launch::LaunchBuilder;
 use libcnb::data::layer_name;
 use libcnb::detect::{DetectContext, DetectResult, DetectResultBuilder};
@@ -24,12 +25,23 @@ use libcnb::generic::{GenericMetadata, GenericPlatform};
 use libcnb::layer_env::Scope;
 use libcnb::Platform;
 use libcnb::{buildpack_main, Buildpack};
+use project_config::AssetsPrecompile;
 use std::io::stdout;
+use std::path::PathBuf;
 
+mod bundler_credentials;
+mod command_timeout;
+mod env_flag;
+mod framework;
+mod gem_audit;
 mod gem_list;
+mod javascript;
 mod layers;
+mod procfile;
+mod project_config;
 mod rake_status;
 mod rake_task_detect;
+mod sbom;
 mod steps;
 mod user_errors;
 
@@ -46,16 +58,15 @@ impl Buildpack for RubyBuildpack {
     type Error = RubyBuildpackError;
 
     fn detect(&self, context: DetectContext<Self>) -> libcnb::Result<DetectResult, Self::Error> {
-        let mut plan_builder = BuildPlanBuilder::new().provides("ruby");
-
-        if let Ok(lockfile) = fs_err::read_to_string(context.app_dir.join("Gemfile.lock")) {
-            plan_builder = plan_builder.requires("ruby");
-        } else if context.app_dir.join("Gemfile").exists() {
-            plan_builder = plan_builder.requires("ruby");
-        }
+        let gemfile_lock = fs_err::read_to_string(context.app_dir.join("Gemfile.lock"))
+            .ok()
+            .map(|lockfile| GemfileLock::from_str(&lockfile).expect("Infallible"));
 
         DetectResultBuilder::pass()
-            .build_plan(plan_builder.build())
+            .build_plan(javascript::build_plan(
+                &context.app_dir,
+                gemfile_lock.as_ref(),
+            ))
             .build()
     }
 
@@ -64,17 +75,23 @@ impl Buildpack for RubyBuildpack {
         let mut logger = BuildLog::new(stdout()).buildpack_name("Heroku Ruby Buildpack");
         let warn_later = WarnGuard::new(stdout());
 
-        // ## Set default environment
-        let (mut env, store) =
-            crate::steps::default_env(&context, &context.platform.env().clone())?;
-
         // Gather static information about project
         let lockfile = context.app_dir.join("Gemfile.lock");
         let lockfile_contents = fs_err::read_to_string(&lockfile)
-            .map_err(|error| RubyBuildpackError::MissingGemfileLock(lockfile, error))?;
-        let gemfile_lock = GemfileLock::from_str(&lockfile_contents).expect("Infallible");
+            .map_err(|error| RubyBuildpackError::MissingGemfileLock(lockfile.clone(), error))?;
+        let mut gemfile_lock = GemfileLock::from_str(&lockfile_contents).expect("Infallible");
         let bundler_version = gemfile_lock.resolve_bundler("2.4.5");
         let ruby_version = gemfile_lock.resolve_ruby("3.1.3");
+        let framework = Framework::detect(&gemfile_lock);
+        let project_config = project_config::ProjectConfig::read(&context.app_dir)
+            .map_err(RubyBuildpackError::ProjectConfigError)?;
+
+        // ## Set default environment
+        let (mut env, store) = crate::steps::default_env(
+            &context,
+            &context.platform.env().clone(),
+            framework.as_ref(),
+        )?;
 
         // ## Install metrics agent
         (logger, env) = {
@@ -138,7 +155,7 @@ impl Buildpack for RubyBuildpack {
                 BundleDownloadLayer {
                     env: env.clone(),
                     metadata: BundleDownloadLayerMetadata {
-                        version: bundler_version,
+                        version: bundler_version.clone(),
                     },
                     _section_logger: section.as_ref(),
                 },
@@ -151,24 +168,45 @@ impl Buildpack for RubyBuildpack {
         // ## Bundle install
         (logger, env) = {
             let section = logger.section("Bundle install");
+            if steps::lockfile_platform(section.as_ref(), &context, &env, &gemfile_lock)?
+                == steps::LockfileChanged::Yes
+            {
+                let contents = fs_err::read_to_string(&lockfile).map_err(|error| {
+                    RubyBuildpackError::MissingGemfileLock(lockfile.clone(), error)
+                })?;
+                gemfile_lock = GemfileLock::from_str(&contents).expect("Infallible");
+            }
+            let gem_cache_layer = context.handle_layer(layer_name!("gem_cache"), GemCacheLayer)?;
+            let without = BundleWithout::new("development:test");
             let bundle_install_layer = context.handle_layer(
                 layer_name!("gems"),
                 BundleInstallLayer {
                     env: env.clone(),
-                    without: BundleWithout::new("development:test"),
+                    without: without.clone(),
+                    gem_cache_path: gem_cache_layer.path,
                     _section_log: section.as_ref(),
+                    sbom: sbom::gems(&gem_list::sbom_specs(
+                        &context.app_dir,
+                        &gemfile_lock,
+                        &env,
+                        &without,
+                    )),
                     metadata: BundleInstallLayerMetadata {
                         stack: context.stack_id.clone(),
                         ruby_version: ruby_version.clone(),
                         force_bundle_install_key: String::from(
                             crate::layers::bundle_install_layer::FORCE_BUNDLE_INSTALL_CACHE_KEY,
                         ),
-                        digest: MetadataDigest::new_env_files(
+                        digest: MetadataDigest::new_env_files_ignoring(
                             &context.platform,
-                            &[
-                                &context.app_dir.join("Gemfile"),
-                                &context.app_dir.join("Gemfile.lock"),
-                            ],
+                            &crate::layers::bundle_install_layer::digest_paths(
+                                &context.app_dir,
+                                &gemfile_lock,
+                            )
+                            .iter()
+                            .map(PathBuf::as_path)
+                            .collect::<Vec<_>>(),
+                            bundler_credentials::is_credential_key,
                         )
                         .map_err(|error| match error {
                             commons::metadata_digest::DigestError::CannotReadFile(path, error) => {
@@ -183,38 +221,147 @@ impl Buildpack for RubyBuildpack {
         };
 
         // ## Detect gems
-        let (mut logger, gem_list, default_process) = {
+        let (mut logger, gem_list, processes) = {
             let section = logger.section("Setting default processes");
 
-            let gem_list = gem_list::GemList::from_bundle_list(&env, section.as_ref())
-                .map_err(RubyBuildpackError::GemListGetError)?;
-            let default_process = steps::get_default_process(section.as_ref(), &context, &gem_list);
+            let gem_list = gem_list::GemList::from_lockfile_or_bundle_list(
+                section.as_ref(),
+                &context.app_dir,
+                &gemfile_lock,
+                &bundler_version,
+                &env,
+            )
+            .map_err(|error| {
+                let redact = bundler_credentials::Redact::from_env(&env);
+                RubyBuildpackError::GemListGetError(redact.cmd_error(error), redact)
+            })?;
+            let processes = steps::get_default_process(
+                section.as_ref(),
+                &context,
+                &gem_list,
+                framework.as_ref(),
+            )?;
 
-            (section.end_section(), gem_list, default_process)
+            (section.end_section(), gem_list, processes)
         };
 
+        // ## Audit gems
+        if env_flag::enabled(&env, steps::HEROKU_GEM_AUDIT) {
+            let section = logger.section("Gem audit");
+            steps::audit_gems(section.as_ref(), &context, &env, &gem_list)?;
+            logger = section.end_section();
+        }
+
         // ## Assets install
-        logger = {
+        let rake_tasks = steps::RakeTasks::new(&project_config, &env);
+        let assets_precompile = steps::assets_precompile_mode(&project_config, &env)?;
+        let assets_dir = steps::assets_dir(&context.app_dir, &project_config, &env);
+        let rake_detect;
+        (logger, rake_detect) = {
             let section = logger.section("Rake assets install");
-            let rake_detect =
-                crate::steps::detect_rake_tasks(section.as_ref(), &gem_list, &context, &env)?;
+            let rake_assets_precompile = fmt::value("rake assets:precompile");
+            if assets_dir != context.app_dir.join("public").join("assets") {
+                log_step(format!(
+                    "Using assets directory {}",
+                    fmt::value(
+                        assets_dir
+                            .strip_prefix(&context.app_dir)
+                            .unwrap_or(&assets_dir)
+                            .to_string_lossy()
+                    )
+                ));
+            }
+            let precompile = match assets_precompile {
+                AssetsPrecompile::Auto if rake_tasks.replace_assets => {
+                    log_step(format!(
+                        "Skipping {rake_assets_precompile} {}",
+                        fmt::details("replaced by configured rake tasks")
+                    ));
+                    false
+                }
+                AssetsPrecompile::Auto => true,
+                AssetsPrecompile::Skip => {
+                    log_step(format!(
+                        "Skipping {rake_assets_precompile} {}",
+                        fmt::details("disabled by configuration")
+                    ));
+                    false
+                }
+                AssetsPrecompile::Manifest => {
+                    steps::use_committed_assets(section.as_ref(), &context, &assets_dir)?;
+                    false
+                }
+            };
+            let asset_task =
+                Some(AssetTask::for_framework(framework.as_ref())).filter(|_| precompile);
 
-            if let Some(rake_detect) = rake_detect {
-                crate::steps::rake_assets_install(section.as_ref(), &context, &env, &rake_detect)?;
+            let rake_detect =
+                match steps::rake_detect_globs(asset_task == Some(AssetTask::Rake), &rake_tasks) {
+                    Some(globs) => steps::detect_rake_tasks(
+                        section.as_ref(),
+                        &gem_list,
+                        &context,
+                        &env,
+                        &assets_dir,
+                        globs,
+                    )?,
+                    None => None,
+                };
+
+            match (asset_task, framework.as_ref()) {
+                (Some(AssetTask::Rake), _) => {
+                    if let Some(rake_detect) = rake_detect.as_ref() {
+                        steps::rake_assets_install(
+                            section.as_ref(),
+                            &context,
+                            &env,
+                            rake_detect,
+                            framework.as_ref(),
+                            &gemfile_lock,
+                            &assets_dir,
+                        )?;
+                    }
+                }
+                (Some(AssetTask::Command(command)), Some(framework)) => {
+                    steps::framework_assets_compile(
+                        section.as_ref(),
+                        &context,
+                        &env,
+                        framework,
+                        command,
+                        &gemfile_lock,
+                        &assets_dir,
+                    )?;
+                }
+                (Some(AssetTask::Command(_)), None) | (None, _) => {}
             }
 
-            section.end_section()
+            (section.end_section(), rake_detect)
         };
+
+        // ## Configured rake tasks
+        if !rake_tasks.tasks.is_empty() {
+            let section = logger.section("Rake tasks");
+            steps::run_rake_tasks(&env, rake_detect.as_ref(), &rake_tasks)?;
+            logger = section.end_section();
+        }
+
+        // ## Bootsnap
+        if gem_list.has("bootsnap") {
+            let section = logger.section("Bootsnap");
+            steps::bootsnap_precompile(section.as_ref(), &context, &env)?;
+            logger = section.end_section();
+        }
         logger.finish_logging();
         warn_later.warn_now();
 
-        if let Some(default_process) = default_process {
+        if processes.is_empty() {
+            BuildResultBuilder::new().store(store).build()
+        } else {
             BuildResultBuilder::new()
-                .launch(LaunchBuilder::new().process(default_process).build())
+                .launch(LaunchBuilder::new().processes(processes).build())
                 .store(store)
                 .build()
-        } else {
-            BuildResultBuilder::new().store(store).build()
         }
     }
 
@@ -231,15 +378,34 @@ fn needs_java(gemfile_lock: &str) -> bool {
 #[derive(Debug)]
 pub(crate) enum RubyBuildpackError {
     RakeDetectError(CmdError),
-    GemListGetError(CmdError),
+    RakeDetectDigestError(std::path::PathBuf, std::io::Error),
+    GemListGetError(CmdError, bundler_credentials::Redact),
     RubyInstallError(RubyInstallError),
     MetricsAgentError(MetricsAgentInstallError),
     MissingGemfileLock(std::path::PathBuf, std::io::Error),
+    LockfileMissingPlatform(String),
+    LockfileAddPlatformError(CmdError),
+    LockfileAddPlatformChangedVersions(Vec<String>),
     InAppDirCacheError(CacheError),
     BundleInstallDigestError(std::path::PathBuf, std::io::Error),
     BundleInstallCommandError(CmdError),
+    GemAuditError(gem_audit::GemAuditError),
+    GemAuditHighSeverity(Vec<String>),
+    GemsLayerPruneError(std::io::Error),
+    ProcfileReadError(std::path::PathBuf, std::io::Error),
+    ProcfileParseError(procfile::ProcfileError),
     RakeAssetsPrecompileFailed(CmdError),
-    GemInstallBundlerCommandError(CmdError),
+    AssetsCompileFailed(CmdError),
+    NodeNotFound(String),
+    ProjectConfigError(project_config::ProjectConfigError),
+    AssetsManifestMissing(std::path::PathBuf),
+    RakeTaskNotFound(String),
+    RakeTasksWithoutRake(Vec<String>),
+    RakeTaskFailed(String, CmdError),
+    BootsnapPrecompileFailed(CmdError),
+    CommandTimedOut(command_timeout::CommandTimedOut),
+    InvalidTimeout(String, String),
+    GemInstallBundlerCommandError(CmdError, bundler_credentials::Redact),
 }
 
 impl From<RubyBuildpackError> for libcnb::Error<RubyBuildpackError> {
@@ -0,0 +1,247 @@
+use core::str::FromStr;
+use libcnb::data::launch::{Process, ProcessBuilder, ProcessType};
+use libcnb::data::process_type;
+
+/// Process types declared in an application's `Procfile`
+///
+/// Each non-blank line is a `<name>: <command>` pair, lines starting with `#` are
+/// comments:
+///
+/// ```text
+/// web: bundle exec puma -C config/puma.rb
+/// worker: bundle exec sidekiq
+/// ```
+///
+/// Simple commands are run directly so the process receives signals such as `SIGTERM`,
+/// with `$PORT` style references rewritten to `$(PORT)` for the launcher to expand.
+/// Commands that need a shell (quotes, pipes, `&&`, redirects, globs, or env var
+/// assignments) are run through `bash -c`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Procfile {
+    pub(crate) entries: Vec<(ProcessType, String)>,
+}
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ProcfileError {
+    #[error("Line {0} is not in the format `<process type>: <command>`: {1:?}")]
+    MalformedLine(usize, String),
+
+    #[error("Line {0} has an invalid process type {1:?}, only letters, numbers, `.`, `_`, and `-` are allowed")]
+    InvalidProcessType(usize, String),
+
+    #[error("Line {0} has an empty command for process type {1:?}")]
+    EmptyCommand(usize, String),
+
+    #[error("Line {0} declares process type {1:?} more than once")]
+    DuplicateProcessType(usize, String),
+}
+
+impl Procfile {
+    /// Launch processes for every entry, the `web` process is the default
+    pub(crate) fn processes(&self) -> Vec<Process> {
+        self.entries
+            .iter()
+            .map(|(name, command)| {
+                let (program, args) = match direct_command(command) {
+                    Some(mut args) => (args.remove(0), args),
+                    None => (
+                        String::from("bash"),
+                        vec![String::from("-c"), command.clone()],
+                    ),
+                };
+                ProcessBuilder::new(name.clone(), [program])
+                    .args(args)
+                    .default(name == &process_type!("web"))
+                    .build()
+            })
+            .collect()
+    }
+}
+
+/// Splits a command that does not need a shell into arguments
+///
+/// Returns `None` when the command uses shell syntax beyond whitespace separated
+/// words and `$VAR` or `${VAR}` references.
+fn direct_command(command: &str) -> Option<Vec<String>> {
+    if command.contains(|c: char| "'\"`\\|&;<>()*?~!#[]".contains(c)) {
+        return None;
+    }
+
+    let args = command
+        .split_whitespace()
+        .map(expand_references)
+        .collect::<Option<Vec<_>>>()?;
+
+    match args.first() {
+        Some(first) if !first.contains('=') && !first.contains('$') => Some(args),
+        _ => None,
+    }
+}
+
+/// Rewrites `$VAR` and `${VAR}` to the launcher's `$(VAR)` syntax
+fn expand_references(word: &str) -> Option<String> {
+    let mut out = String::new();
+    let mut chars = word.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            if c == '{' || c == '}' {
+                return None;
+            }
+            out.push(c);
+            continue;
+        }
+
+        let braced = chars.next_if_eq(&'{').is_some();
+        let mut name = String::new();
+        while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+            name.push(c);
+        }
+        if name.is_empty() || (braced && chars.next() != Some('}')) {
+            return None;
+        }
+        out.push_str("$(");
+        out.push_str(&name);
+        out.push(')');
+    }
+    Some(out)
+}
+
+impl FromStr for Procfile {
+    type Err = ProcfileError;
+
+    fn from_str(contents: &str) -> Result<Self, Self::Err> {
+        let mut entries: Vec<(ProcessType, String)> = Vec::new();
+
+        for (index, line) in contents.lines().enumerate() {
+            let number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (name, command) = line
+                .split_once(':')
+                .ok_or_else(|| ProcfileError::MalformedLine(number, line.to_string()))?;
+            let (name, command) = (name.trim(), command.trim());
+
+            let process_type = ProcessType::from_str(name)
+                .map_err(|_| ProcfileError::InvalidProcessType(number, name.to_string()))?;
+            if command.is_empty() {
+                return Err(ProcfileError::EmptyCommand(number, name.to_string()));
+            }
+            if entries
+                .iter()
+                .any(|(existing, _)| existing == &process_type)
+            {
+                return Err(ProcfileError::DuplicateProcessType(
+                    number,
+                    name.to_string(),
+                ));
+            }
+
+            entries.push((process_type, command.to_string()));
+        }
+
+        Ok(Procfile { entries })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_procfile() {
+        let procfile = Procfile::from_str(
+            r#"
+# Comments and blank lines are ignored
+
+web: bundle exec puma -C config/puma.rb
+worker:bundle exec sidekiq -q "default"
+release: bin/rails db:migrate
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            vec![
+                (
+                    process_type!("web"),
+                    String::from("bundle exec puma -C config/puma.rb")
+                ),
+                (
+                    process_type!("worker"),
+                    String::from(r#"bundle exec sidekiq -q "default""#)
+                ),
+                (
+                    process_type!("release"),
+                    String::from("bin/rails db:migrate")
+                ),
+            ],
+            procfile.entries
+        );
+
+        let processes = procfile.processes();
+        assert_eq!(3, processes.len());
+        assert!(processes[0].default);
+        assert_eq!(vec!["bundle"], processes[0].command);
+        assert_eq!(
+            vec!["exec", "puma", "-C", "config/puma.rb"],
+            processes[0].args
+        );
+        assert!(!processes[1].default);
+        assert_eq!(vec!["bash"], processes[1].command);
+        assert_eq!(
+            vec!["-c", r#"bundle exec sidekiq -q "default""#],
+            processes[1].args
+        );
+    }
+
+    #[test]
+    fn test_direct_command() {
+        assert_eq!(
+            Some(vec![
+                String::from("bin/rails"),
+                String::from("server"),
+                String::from("-p"),
+                String::from("$(PORT)"),
+                String::from("--bind=tcp://0.0.0.0:$(PORT)"),
+            ]),
+            direct_command("bin/rails server -p $PORT --bind=tcp://0.0.0.0:${PORT}")
+        );
+
+        assert_eq!(
+            None,
+            direct_command("bin/rails db:migrate && bin/rails server")
+        );
+        assert_eq!(None, direct_command("RAILS_MAX_THREADS=5 bin/rails server"));
+        assert_eq!(None, direct_command("bin/rails server -p ${PORT:-3000}"));
+        assert_eq!(None, direct_command("bin/rails server > log/server.log"));
+    }
+
+    #[test]
+    fn test_malformed_procfile() {
+        assert_eq!(
+            Err(ProcfileError::MalformedLine(
+                2,
+                String::from("bundle exec puma")
+            )),
+            Procfile::from_str("web: bin/rails server\nbundle exec puma\n")
+        );
+        assert_eq!(
+            Err(ProcfileError::InvalidProcessType(
+                1,
+                String::from("my worker")
+            )),
+            Procfile::from_str("my worker: bundle exec sidekiq")
+        );
+        assert_eq!(
+            Err(ProcfileError::EmptyCommand(1, String::from("web"))),
+            Procfile::from_str("web:")
+        );
+        assert_eq!(
+            Err(ProcfileError::DuplicateProcessType(2, String::from("web"))),
+            Procfile::from_str("web: bin/rails server\nweb: bundle exec puma")
+        );
+    }
+}
@@ -0,0 +1,204 @@
+use commons::display::SentenceList;
+use core::str::FromStr;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// Ruby buildpack settings from the app's `project.toml`
+///
+/// Settings live under the buildpack's own table so they don't collide with other
+/// buildpacks:
+///
+/// ```toml
+/// [com.heroku.buildpacks.ruby]
+/// rake_tasks = ["bootsnap:precompile", "sitemap:generate"]
+/// rake_tasks_replace_assets = false
+/// assets_precompile = "auto"
+/// assets_prefix = "/assets"
+/// ```
+///
+/// A missing `project.toml`, or one without this table, uses the defaults.
+#[derive(Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ProjectConfig {
+    /// Rake tasks to run, in order, after `assets:precompile`
+    #[serde(default)]
+    pub(crate) rake_tasks: Vec<String>,
+
+    /// Run `rake_tasks` instead of `assets:precompile`
+    #[serde(default)]
+    pub(crate) rake_tasks_replace_assets: bool,
+
+    /// Whether to run `assets:precompile`
+    #[serde(default)]
+    pub(crate) assets_precompile: AssetsPrecompile,
+
+    /// Rails `config.assets.prefix`, detected from `config/` when not set
+    #[serde(default)]
+    pub(crate) assets_prefix: Option<String>,
+}
+
+/// Whether to run `assets:precompile`
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum AssetsPrecompile {
+    /// Run it unless a manifest is committed in `public/assets`
+    #[default]
+    Auto,
+    /// Never run it, i.e. for API only apps
+    Skip,
+    /// Never run it and require a manifest committed in `public/assets`
+    Manifest,
+}
+
+impl AssetsPrecompile {
+    /// Values accepted from `project.toml` and `HEROKU_ASSETS_PRECOMPILE`
+    pub(crate) const VALUES: &'static [&'static str] = &["auto", "skip", "manifest"];
+}
+
+impl FromStr for AssetsPrecompile {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "auto" => Ok(AssetsPrecompile::Auto),
+            "skip" => Ok(AssetsPrecompile::Skip),
+            "manifest" => Ok(AssetsPrecompile::Manifest),
+            _ => Err(value.to_string()),
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub(crate) enum ProjectConfigError {
+    #[error("Could not read {0}: {1}")]
+    Read(PathBuf, std::io::Error),
+
+    #[error("Could not parse {0}: {1}")]
+    Parse(PathBuf, toml::de::Error),
+
+    #[error("Invalid value {1:?} for {0}, expected one of {}", expected_values(.2))]
+    InvalidEnv(String, String, &'static [&'static str]),
+}
+
+/// Formats allowed values for an error i.e. `` `auto`, `skip`, or `manifest` ``
+pub(crate) fn expected_values(values: &[&str]) -> String {
+    let values = values
+        .iter()
+        .map(|value| format!("`{value}`"))
+        .collect::<Vec<_>>();
+    SentenceList::new(&values).join_str("or").to_string()
+}
+
+#[derive(Deserialize, Default)]
+struct ProjectToml {
+    #[serde(default)]
+    com: Com,
+}
+
+#[derive(Deserialize, Default)]
+struct Com {
+    #[serde(default)]
+    heroku: Heroku,
+}
+
+#[derive(Deserialize, Default)]
+struct Heroku {
+    #[serde(default)]
+    buildpacks: Buildpacks,
+}
+
+#[derive(Deserialize, Default)]
+struct Buildpacks {
+    #[serde(default)]
+    ruby: ProjectConfig,
+}
+
+impl ProjectConfig {
+    /// # Errors
+    ///
+    /// Errors if `project.toml` exists but cannot be read or parsed.
+    pub(crate) fn read(app_dir: &Path) -> Result<Self, ProjectConfigError> {
+        let path = app_dir.join("project.toml");
+        if !path.exists() {
+            return Ok(ProjectConfig::default());
+        }
+
+        let contents = fs_err::read_to_string(&path)
+            .map_err(|error| ProjectConfigError::Read(path.clone(), error))?;
+        toml::from_str::<ProjectToml>(&contents)
+            .map(|project| project.com.heroku.buildpacks.ruby)
+            .map_err(|error| ProjectConfigError::Parse(path, error))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_read_project_config() {
+        let tmp = tempfile::tempdir().unwrap();
+        assert_eq!(
+            ProjectConfig::default(),
+            ProjectConfig::read(tmp.path()).unwrap()
+        );
+
+        fs_err::write(
+            tmp.path().join("project.toml"),
+            r#"
+[_]
+schema-version = "0.2"
+
+[com.heroku.buildpacks.ruby]
+rake_tasks = ["bootsnap:precompile", "sitemap:generate"]
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            ProjectConfig {
+                rake_tasks: vec![
+                    String::from("bootsnap:precompile"),
+                    String::from("sitemap:generate")
+                ],
+                rake_tasks_replace_assets: false,
+                assets_precompile: AssetsPrecompile::Auto,
+                assets_prefix: None,
+            },
+            ProjectConfig::read(tmp.path()).unwrap()
+        );
+
+        fs_err::write(
+            tmp.path().join("project.toml"),
+            "[com.heroku.buildpacks.ruby]\nassets_precompile = \"skip\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            AssetsPrecompile::Skip,
+            ProjectConfig::read(tmp.path()).unwrap().assets_precompile
+        );
+        assert_eq!(
+            Ok(AssetsPrecompile::Manifest),
+            AssetsPrecompile::from_str("Manifest")
+        );
+        assert!(AssetsPrecompile::from_str("never").is_err());
+        assert_eq!(
+            "Invalid value \"never\" for HEROKU_ASSETS_PRECOMPILE, expected one of `auto`, `skip`, or `manifest`",
+            ProjectConfigError::InvalidEnv(
+                String::from("HEROKU_ASSETS_PRECOMPILE"),
+                String::from("never"),
+                AssetsPrecompile::VALUES
+            )
+            .to_string()
+        );
+
+        fs_err::write(
+            tmp.path().join("project.toml"),
+            "[com.heroku.buildpacks.ruby]\nrake_task = \"typo\"\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            ProjectConfig::read(tmp.path()),
+            Err(ProjectConfigError::Parse(_, _))
+        ));
+    }
+}
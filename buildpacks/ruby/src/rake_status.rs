@@ -2,18 +2,71 @@ use crate::gem_list::GemList;
 use std::path::{Path, PathBuf};
 
 /// Determine if an application is ready to run a rake task or not
+///
+/// Manifest globs are matched in `assets_dir`, see `assets_dir`.
 pub fn check_rake_ready(
     app_path: &Path,
     gem_list: &GemList,
+    assets_dir: &Path,
     globs: impl IntoIterator<Item = impl AsRef<str>>,
 ) -> RakeStatus {
     let rakefile = find_rakefile(app_path);
     let rake_gem = rake_gem(gem_list);
-    let manifest = asset_manifest_from_glob(app_path, globs);
+    let manifest = asset_manifest_from_glob(assets_dir, globs);
 
     rake_status(&rake_gem, rakefile, manifest)
 }
 
+/// Manifest files that indicate precompiled assets are committed to git
+///
+/// Sprockets 4, Sprockets 3 and earlier, and Propshaft respectively.
+pub const ASSET_MANIFEST_GLOBS: [&str; 3] = [
+    ".sprockets-manifest-*.json",
+    "manifest-*.json",
+    ".manifest.json",
+];
+
+/// Default for `config.assets.prefix`
+pub const DEFAULT_ASSETS_PREFIX: &str = "/assets";
+
+/// Directory compiled assets are written to for an asset prefix, i.e. `public/assets`
+pub fn assets_dir(app_path: &Path, prefix: &str) -> PathBuf {
+    prefix
+        .split('/')
+        .filter(|part| !part.is_empty())
+        .fold(app_path.join("public"), |dir, part| dir.join(part))
+}
+
+/// Reads a static `config.assets.prefix = "/..."` from the Rails production config
+///
+/// Later files take precedence, matching the order Rails loads them in.
+pub fn detect_assets_prefix(app_path: &Path) -> Option<String> {
+    let prefix_re = regex::Regex::new(r#"(?m)^\s*config\.assets\.prefix\s*=\s*["']([^"'#]+)["']"#)
+        .expect("Internal error: regex");
+
+    ["application.rb", "environments/production.rb"]
+        .iter()
+        .rev()
+        .filter_map(|name| fs_err::read_to_string(app_path.join("config").join(name)).ok())
+        .find_map(|contents| {
+            prefix_re
+                .captures_iter(&contents)
+                .last()
+                .map(|captures| captures[1].to_string())
+        })
+}
+
+/// Returns asset manifest files in `assets_dir` matching the globs
+pub fn find_asset_manifests(
+    assets_dir: &Path,
+    globs: impl IntoIterator<Item = impl AsRef<str>>,
+) -> Vec<PathBuf> {
+    match asset_manifest_from_glob(assets_dir, globs) {
+        AssetManifest::Found(manifests) => manifests,
+        AssetManifest::Missing => Vec::new(),
+    }
+}
+
 #[derive(Debug, Eq, PartialEq)]
 pub enum RakeStatus {
     Ready(PathBuf),
@@ -71,15 +124,13 @@ fn rake_gem(gem_list: &GemList) -> RakeGem {
 }
 
 fn asset_manifest_from_glob(
-    app_dir: &Path,
+    assets_dir: &Path,
     globs: impl IntoIterator<Item = impl AsRef<str>>,
 ) -> AssetManifest {
     let manifests = globs
         .into_iter()
         .map(|glob_pattern| {
-            app_dir
-                .join("public")
-                .join("assets")
+            assets_dir
                 .join(glob_pattern.as_ref())
                 .into_os_string()
                 .into_string()
@@ -112,9 +163,10 @@ mod tests {
 
     // Checks in public/assets if an existing manifest file exists
     fn asset_manifest(app_dir: &Path) -> AssetManifest {
-        let globs = [".sprockets-manifest-*.json", "manifest-*.json"];
-
-        asset_manifest_from_glob(app_dir, globs)
+        asset_manifest_from_glob(
+            &assets_dir(app_dir, DEFAULT_ASSETS_PREFIX),
+            ASSET_MANIFEST_GLOBS,
+        )
     }
 
     #[test]
@@ -198,6 +250,37 @@ mod tests {
                 AssetManifest::Found(vec![path.clone()])
             );
         });
+
+        touch_file(&assets_dir.join(".manifest.json"), |path| {
+            assert_eq!(
+                asset_manifest(tmpdir.path()),
+                AssetManifest::Found(vec![path.clone()])
+            );
+        });
+    }
+
+    #[test]
+    fn test_assets_prefix() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let app_dir = tmpdir.path();
+        assert_eq!(
+            app_dir.join("public").join("assets"),
+            assets_dir(app_dir, DEFAULT_ASSETS_PREFIX)
+        );
+        assert_eq!(
+            app_dir.join("public").join("static").join("compiled"),
+            assets_dir(app_dir, "/static/compiled/")
+        );
+
+        assert_eq!(None, detect_assets_prefix(app_dir));
+        touch_file(&app_dir.join("config").join("application.rb"), |path| {
+            fs_err::write(
+                path,
+                "module App\n  class Application < Rails::Application\n    # config.assets.prefix = \"/nope\"\n    config.assets.prefix = '/static'\n  end\nend\n",
+            )
+            .unwrap();
+            assert_eq!(Some(String::from("/static")), detect_assets_prefix(app_dir));
+        });
     }
 
     #[test]
@@ -3,59 +3,114 @@ use commons::output::{
     section_log::{log_step_timed, SectionLogger},
 };
 
+use crate::command_timeout::{self, TimedCmdError, Timeout};
 use core::str::FromStr;
 use fun_run::{CmdError, CommandWithName};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet, VecDeque};
 use std::{ffi::OsStr, process::Command};
 
 /// Run `rake -P` and parse output to show what rake tasks an application has
 ///
-/// ```rust,no_run
-/// use commons::rake_task_detect::RakeDetect;
+/// The output lists each task followed by its indented prerequisites:
+///
+/// ```text
+/// rake assets:precompile
+///     environment
+///     yarn:install
+/// ```
+///
+/// This is parsed into a graph of task names to their prerequisites:
+///
+/// ```rust,ignore
+/// use crate::command_timeout::TimedCommand;
+/// use crate::rake_task_detect::RakeDetect;
+/// use commons::output::build_log::*;
 /// use libcnb::Env;
 ///
-/// let rake_detect = RakeDetect::from_rake_command(&Env::new(), false).unwrap();
+/// let env = Env::new();
+/// let section = BuildLog::new(std::io::stdout())
+///     .buildpack_name("Heroku Ruby Buildpack")
+///     .section("Rake assets install");
+/// let timeout = TimedCommand::RakeDetect.timeout(&env).unwrap();
+/// let rake_detect =
+///     RakeDetect::from_rake_command(section.as_ref(), &env, false, timeout).unwrap();
 /// assert!(!rake_detect.has_task("assets:precompile"));
+/// assert!(!rake_detect.depends_on("assets:precompile", "yarn:install"));
 /// ```
-#[derive(Default)]
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct RakeDetect {
-    output: String,
+    tasks: BTreeMap<String, Vec<String>>,
 }
 
 impl RakeDetect {
     /// # Errors
     ///
-    /// Will return `Err` if `bundle exec rake -p` command cannot be invoked by the operating system.
-    pub fn from_rake_command<T: IntoIterator<Item = (K, V)>, K: AsRef<OsStr>, V: AsRef<OsStr>>(
+    /// Will return `Err` if `bundle exec rake -p` command cannot be invoked by the operating system,
+    /// or does not finish within the timeout.
+    pub(crate) fn from_rake_command<
+        T: IntoIterator<Item = (K, V)>,
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    >(
         _logger: &dyn SectionLogger,
         envs: T,
         error_on_failure: bool,
-    ) -> Result<Self, CmdError> {
+        timeout: Timeout,
+    ) -> Result<Self, TimedCmdError> {
         let mut cmd = Command::new("bundle");
         cmd.args(["exec", "rake", "-P", "--trace"])
             .env_clear()
             .envs(envs);
 
         log_step_timed(format!("Running {}", fmt::command(cmd.name())), || {
-            cmd.named_output()
+            command_timeout::named_output(&mut cmd, timeout)
         })
         .or_else(|error| {
             if error_on_failure {
                 Err(error)
             } else {
                 match error {
-                    CmdError::SystemError(_, _) => Err(error),
-                    CmdError::NonZeroExitNotStreamed(output)
-                    | CmdError::NonZeroExitAlreadyStreamed(output) => Ok(output),
+                    TimedCmdError::Cmd(
+                        CmdError::NonZeroExitNotStreamed(output)
+                        | CmdError::NonZeroExitAlreadyStreamed(output),
+                    ) => Ok(output),
+                    TimedCmdError::Cmd(CmdError::SystemError(_, _))
+                    | TimedCmdError::TimedOut(_) => Err(error),
                 }
             }
         })
-        .and_then(|output| RakeDetect::from_str(&output.stdout_lossy()))
+        .and_then(|output| RakeDetect::from_str(&output.stdout_lossy()).map_err(TimedCmdError::Cmd))
     }
 
+    /// True when the task is defined, prerequisites of other tasks do not count
     #[must_use]
-    pub fn has_task(&self, string: &str) -> bool {
-        let task_re = regex::Regex::new(&format!("\\s{string}")).expect("clippy");
-        task_re.is_match(&self.output)
+    pub fn has_task(&self, name: &str) -> bool {
+        self.tasks.contains_key(name)
+    }
+
+    /// Direct prerequisites of a task, `None` if the task is not defined
+    #[must_use]
+    pub fn prerequisites(&self, name: &str) -> Option<&[String]> {
+        self.tasks.get(name).map(Vec::as_slice)
+    }
+
+    /// True when `prerequisite` runs before `name`, directly or through other prerequisites
+    #[must_use]
+    pub fn depends_on(&self, name: &str, prerequisite: &str) -> bool {
+        let mut seen = HashSet::new();
+        let mut queue = VecDeque::from([name]);
+        while let Some(task) = queue.pop_front() {
+            for dependency in self.prerequisites(task).unwrap_or_default() {
+                if dependency == prerequisite {
+                    return true;
+                }
+                if seen.insert(dependency.as_str()) {
+                    queue.push_back(dependency);
+                }
+            }
+        }
+        false
     }
 }
 
@@ -63,9 +118,24 @@ impl FromStr for RakeDetect {
     type Err = CmdError;
 
     fn from_str(string: &str) -> Result<Self, Self::Err> {
-        Ok(RakeDetect {
-            output: string.to_lowercase(),
-        })
+        let mut tasks = BTreeMap::<String, Vec<String>>::new();
+        let mut current = None;
+
+        for line in string.lines() {
+            if let Some(name) = line.strip_prefix("rake ") {
+                let name = name.trim().to_string();
+                tasks.entry(name.clone()).or_default();
+                current = Some(name);
+            } else if line.starts_with(char::is_whitespace) && !line.trim().is_empty() {
+                if let Some(task) = current.as_ref().and_then(|name| tasks.get_mut(name)) {
+                    task.push(line.trim().to_string());
+                }
+            } else {
+                current = None;
+            }
+        }
+
+        Ok(RakeDetect { tasks })
     }
 }
 
@@ -122,5 +192,39 @@ rake assets:precompile
         .unwrap();
 
         assert!(rake_detect.has_task("assets:precompile"));
+        assert!(!rake_detect.has_task("environment"));
+        assert!(!rake_detect.has_task("assets:pre"));
+        assert!(!rake_detect.has_task("update:configs"));
+
+        assert_eq!(
+            Some([String::from("environment"), String::from("yarn:install")].as_slice()),
+            rake_detect.prerequisites("assets:precompile")
+        );
+        assert_eq!(
+            Some([].as_slice()),
+            rake_detect.prerequisites("assets:bench")
+        );
+        assert_eq!(None, rake_detect.prerequisites("yarn:install"));
+
+        assert!(rake_detect.depends_on("assets:precompile", "yarn:install"));
+        assert!(!rake_detect.depends_on("assets:clean", "yarn:install"));
+    }
+
+    #[test]
+    fn test_transitive_prerequisites() {
+        let rake_detect = RakeDetect::from_str(
+            r"
+rake assets:precompile
+    javascript:build
+rake javascript:build
+    javascript:install
+rake javascript:install
+    javascript:build
+",
+        )
+        .unwrap();
+
+        assert!(rake_detect.depends_on("assets:precompile", "javascript:install"));
+        assert!(!rake_detect.depends_on("assets:precompile", "css:build"));
     }
 }
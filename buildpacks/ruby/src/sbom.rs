@@ -0,0 +1,139 @@
+use commons::gemfile_lock::{LockedSpec, ResolvedBundlerVersion, ResolvedRubyVersion};
+use libcnb::data::sbom::SbomFormat;
+use libcnb::sbom::Sbom;
+use serde_json::json;
+
+/// # Software Bill of Materials for the Ruby, bundler, and gems layers
+///
+/// Written in `CycloneDX` 1.3 JSON, the same version libcnb uses, so `pack sbom download`
+/// lists each installed component. Gems use a `pkg:gem` package URL:
+///
+/// - Spec: <https://github.com/package-url/purl-spec/blob/master/PURL-TYPES.rst#gem>
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Component {
+    kind: &'static str,
+    name: String,
+    version: String,
+    purl: String,
+}
+
+/// SBOM for the Ruby interpreter
+pub(crate) fn ruby(version: &ResolvedRubyVersion) -> Sbom {
+    let (name, version) = match version.0.split_once("-jruby-") {
+        Some((_ruby, jruby)) => ("jruby", jruby.to_string()),
+        None => ("ruby", version.to_string()),
+    };
+
+    cyclonedx(&[Component {
+        kind: "application",
+        name: name.to_string(),
+        purl: format!("pkg:generic/{name}@{version}"),
+        version,
+    }])
+}
+
+/// SBOM for the bundler gem
+pub(crate) fn bundler(version: &ResolvedBundlerVersion) -> Sbom {
+    cyclonedx(&[gem_component("bundler", &version.to_string(), None)])
+}
+
+/// SBOM for the gems installed by `bundle install`
+pub(crate) fn gems(specs: &[&LockedSpec]) -> Sbom {
+    let mut components = specs
+        .iter()
+        .map(|spec| gem_component(&spec.name, &spec.version, spec.platform.as_deref()))
+        .collect::<Vec<_>>();
+    components.sort_by(|a, b| a.name.cmp(&b.name));
+
+    cyclonedx(&components)
+}
+
+fn gem_component(name: &str, version: &str, platform: Option<&str>) -> Component {
+    let qualifiers = platform
+        .map(|platform| format!("?platform={platform}"))
+        .unwrap_or_default();
+
+    Component {
+        kind: "library",
+        name: name.to_string(),
+        version: version.to_string(),
+        purl: format!("pkg:gem/{name}@{version}{qualifiers}"),
+    }
+}
+
+fn cyclonedx(components: &[Component]) -> Sbom {
+    let document = json!({
+        "bomFormat": "CycloneDX",
+        "specVersion": "1.3",
+        "version": 1,
+        "components": components
+            .iter()
+            .map(|component| {
+                json!({
+                    "type": component.kind,
+                    "name": component.name,
+                    "version": component.version,
+                    "purl": component.purl,
+                })
+            })
+            .collect::<Vec<_>>(),
+    });
+
+    Sbom::from_bytes(
+        SbomFormat::CycloneDxJson,
+        serde_json::to_vec_pretty(&document).expect("Internal error: JSON serialization"),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse_components(sbom: &Sbom) -> Vec<serde_json::Value> {
+        let document: serde_json::Value = serde_json::from_slice(&sbom.data).unwrap();
+        assert_eq!("CycloneDX", document["bomFormat"]);
+        document["components"].as_array().unwrap().clone()
+    }
+
+    #[test]
+    fn test_ruby_sbom() {
+        let sbom = ruby(&ResolvedRubyVersion(String::from("3.2.2")));
+        assert_eq!(SbomFormat::CycloneDxJson, sbom.format);
+        let components = parse_components(&sbom);
+        assert_eq!("ruby", components[0]["name"]);
+        assert_eq!("pkg:generic/ruby@3.2.2", components[0]["purl"]);
+
+        let sbom = ruby(&ResolvedRubyVersion(String::from("2.6.8-jruby-9.3.6.0")));
+        assert_eq!(
+            "pkg:generic/jruby@9.3.6.0",
+            parse_components(&sbom)[0]["purl"]
+        );
+    }
+
+    #[test]
+    fn test_gems_sbom() {
+        let spec = |name: &str, version: &str, platform: Option<&str>| LockedSpec {
+            name: name.to_string(),
+            version: version.to_string(),
+            platform: platform.map(String::from),
+            revision: None,
+            dependencies: Vec::new(),
+        };
+        let rack = spec("rack", "3.0.0", None);
+        let nokogiri = spec("nokogiri", "1.15.4", Some("x86_64-linux"));
+
+        let components = parse_components(&gems(&[&rack, &nokogiri]));
+        assert_eq!(2, components.len());
+        assert_eq!("nokogiri", components[0]["name"]);
+        assert_eq!(
+            "pkg:gem/nokogiri@1.15.4?platform=x86_64-linux",
+            components[0]["purl"]
+        );
+        assert_eq!("3.0.0", components[1]["version"]);
+        assert_eq!("pkg:gem/rack@3.0.0", components[1]["purl"]);
+
+        let components =
+            parse_components(&bundler(&ResolvedBundlerVersion(String::from("2.4.17"))));
+        assert_eq!("pkg:gem/bundler@2.4.17", components[0]["purl"]);
+    }
+}
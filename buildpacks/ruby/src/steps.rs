@@ -1,9 +1,22 @@
+mod audit_gems;
+mod bootsnap_precompile;
 mod default_env;
 mod detect_rake_tasks;
 mod get_default_process;
+mod lockfile_platform;
 mod rake_assets_install;
+mod run_rake_tasks;
 
+pub(crate) use self::audit_gems::{audit_gems, HEROKU_GEM_AUDIT};
+pub(crate) use self::bootsnap_precompile::bootsnap_precompile;
 pub(crate) use self::default_env::default_env;
-pub(crate) use self::detect_rake_tasks::detect_rake_tasks;
+pub(crate) use self::detect_rake_tasks::{detect_rake_tasks, rake_detect_globs};
 pub(crate) use self::get_default_process::get_default_process;
-pub(crate) use self::rake_assets_install::rake_assets_install;
+pub(crate) use self::lockfile_platform::{
+    build_platform, lockfile_platform, satisfies_platform, LockfileChanged,
+};
+pub(crate) use self::rake_assets_install::{
+    assets_dir, assets_precompile_mode, framework_assets_compile, is_database_error,
+    rake_assets_install, use_committed_assets,
+};
+pub(crate) use self::run_rake_tasks::{run_rake_tasks, RakeTasks};
@@ -0,0 +1,120 @@
+use crate::env_flag;
+use crate::gem_audit::{self, Severity};
+use crate::gem_list::GemList;
+use crate::RubyBuildpack;
+use crate::RubyBuildpackError;
+use commons::output::{
+    fmt::{self, HELP},
+    section_log::{log_step, log_warning_later, SectionLogger},
+};
+use libcnb::build::BuildContext;
+use libcnb::Env;
+use std::path::PathBuf;
+
+/// Opt-in to checking locked gems against security advisories
+pub(crate) const HEROKU_GEM_AUDIT: &str = "HEROKU_GEM_AUDIT";
+
+/// Path to a `ruby-advisory-db` checkout, relative paths are from the app root. Required,
+/// no database is bundled with the buildpack
+pub(crate) const HEROKU_GEM_AUDIT_DB_PATH: &str = "HEROKU_GEM_AUDIT_DB_PATH";
+
+/// Fail the build on findings with a high or critical severity
+pub(crate) const HEROKU_GEM_AUDIT_FAIL_ON_HIGH: &str = "HEROKU_GEM_AUDIT_FAIL_ON_HIGH";
+
+/// Checks locked gems against a local advisory database without network access
+///
+/// Findings are reported as warnings at the end of the build. When
+/// `HEROKU_GEM_AUDIT_FAIL_ON_HIGH` is enabled, high and critical findings fail the build.
+pub(crate) fn audit_gems(
+    _logger: &dyn SectionLogger,
+    context: &BuildContext<RubyBuildpack>,
+    env: &Env,
+    gem_list: &GemList,
+) -> Result<(), RubyBuildpackError> {
+    let help = || {
+        log_step(format!(
+            "{HELP} Set {} to the path of a {} checkout",
+            fmt::value(HEROKU_GEM_AUDIT_DB_PATH),
+            fmt::value("ruby-advisory-db")
+        ));
+    };
+    let Some(database) = database_path(context, env) else {
+        log_step(format!(
+            "Skipping gem audit {}",
+            fmt::details(format!("{} not set", fmt::value(HEROKU_GEM_AUDIT_DB_PATH)))
+        ));
+        help();
+        return Ok(());
+    };
+    if !database.join("gems").is_dir() {
+        log_step(format!(
+            "Skipping gem audit {}",
+            fmt::details(format!(
+                "no advisory database at {}",
+                fmt::value(database.to_string_lossy())
+            ))
+        ));
+        help();
+        return Ok(());
+    }
+
+    log_step(format!(
+        "Checking gems against advisories in {}",
+        fmt::value(database.to_string_lossy())
+    ));
+    let advisories = gem_audit::load_advisories(&database, gem_list)
+        .map_err(RubyBuildpackError::GemAuditError)?;
+    let findings = gem_audit::audit(gem_list, &advisories);
+
+    if findings.is_empty() {
+        log_step("No vulnerable gems found");
+        return Ok(());
+    }
+
+    let high = findings
+        .iter()
+        .filter(|finding| finding.advisory.severity() >= Severity::High)
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    log_step(format!(
+        "Found {} vulnerable gem(s), {} high severity",
+        findings.len(),
+        high.len()
+    ));
+
+    if env_flag::enabled(env, HEROKU_GEM_AUDIT_FAIL_ON_HIGH) && !high.is_empty() {
+        return Err(RubyBuildpackError::GemAuditHighSeverity(high));
+    }
+
+    let list = findings
+        .iter()
+        .map(|finding| {
+            let url = finding
+                .advisory
+                .url
+                .as_ref()
+                .map(|url| format!("\n    {}", fmt::url(url)))
+                .unwrap_or_default();
+            format!("  - {finding}{url}")
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    log_warning_later(format!(
+        "Vulnerable gems found\n\n\
+        The following gems in your {} have known security advisories:\n\n\
+        {list}\n\n\
+        Upgrade these gems to a patched version. To fail the build when a\n\
+        high severity advisory is found set {}.",
+        fmt::value("Gemfile.lock"),
+        fmt::value(format!("{HEROKU_GEM_AUDIT_FAIL_ON_HIGH}=1"))
+    ));
+
+    Ok(())
+}
+
+fn database_path(context: &BuildContext<RubyBuildpack>, env: &Env) -> Option<PathBuf> {
+    env.get(HEROKU_GEM_AUDIT_DB_PATH)
+        .filter(|path| !path.is_empty())
+        .map(|path| context.app_dir.join(path))
+}
@@ -0,0 +1,94 @@
+use crate::command_timeout::{self, TimedCommand};
+use crate::RubyBuildpack;
+use crate::RubyBuildpackError;
+use commons::cache::{mib, AppCacheCollection, CacheConfig, KeepPath, OverLimit};
+use commons::output::{
+    fmt,
+    section_log::{log_step_stream, SectionLogger},
+};
+use fun_run::{self, CommandWithName};
+use libcnb::build::BuildContext;
+use libcnb::Env;
+use std::path::Path;
+use std::process::Command;
+
+/// Application directories compiled by `bootsnap precompile`, when present
+const BOOTSNAP_DIRS: [&str; 2] = ["app", "lib"];
+
+/// Compiles Ruby and YAML files into the bootsnap cache so the app boots faster
+///
+/// The cache in `tmp/cache/bootsnap` is kept between builds so only changed files are
+/// recompiled, and kept at runtime where `bootsnap/setup` reads it.
+///
+/// # Errors
+///
+/// Errors if the cache cannot be loaded or stored, or if `bootsnap precompile` fails.
+pub(crate) fn bootsnap_precompile(
+    logger: &dyn SectionLogger,
+    context: &BuildContext<RubyBuildpack>,
+    env: &Env,
+) -> Result<(), RubyBuildpackError> {
+    let cache = AppCacheCollection::new_and_load(
+        context,
+        [CacheConfig {
+            path: context.app_dir.join("tmp").join("cache").join("bootsnap"),
+            limit: mib(200),
+            over_limit: OverLimit::RemoveOldest,
+            keep_path: KeepPath::Runtime,
+            invalidate_on: Vec::new(),
+        }],
+        logger,
+    )
+    .map_err(RubyBuildpackError::InAppDirCacheError)?;
+
+    let timeout = TimedCommand::BootsnapPrecompile.timeout(env)?;
+    let path_env = env.get("PATH").cloned();
+    let mut cmd = Command::new("bundle");
+    cmd.args(bootsnap_args(&context.app_dir))
+        .env_clear()
+        .envs(env);
+
+    log_step_stream(format!("Running {}", fmt::command(cmd.name())), |stream| {
+        command_timeout::stream_output(&mut cmd, stream.io(), stream.io(), timeout)
+    })
+    .map_err(|error| error.map_cmd(|error| fun_run::map_which_problem(error, &mut cmd, path_env)))
+    .map_err(|error| error.into_buildpack_error(RubyBuildpackError::BootsnapPrecompileFailed))?;
+
+    cache
+        .save_and_clean()
+        .map_err(RubyBuildpackError::InAppDirCacheError)
+}
+
+/// Precompiles gems from the `Gemfile` and each app directory that exists
+fn bootsnap_args(app_dir: &Path) -> Vec<String> {
+    ["exec", "bootsnap", "precompile", "--gemfile"]
+        .into_iter()
+        .map(String::from)
+        .chain(
+            BOOTSNAP_DIRS
+                .into_iter()
+                .filter(|dir| app_dir.join(dir).is_dir())
+                .map(|dir| format!("{dir}/")),
+        )
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_bootsnap_args() {
+        let tmp = tempfile::tempdir().unwrap();
+        assert_eq!(
+            vec!["exec", "bootsnap", "precompile", "--gemfile"],
+            bootsnap_args(tmp.path())
+        );
+
+        fs_err::create_dir_all(tmp.path().join("app")).unwrap();
+        assert_eq!(
+            vec!["exec", "bootsnap", "precompile", "--gemfile", "app/"],
+            bootsnap_args(tmp.path())
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use crate::framework::Framework;
 use crate::{RubyBuildpack, RubyBuildpackError};
 use commons::layer::DefaultEnvLayer;
 use libcnb::{
@@ -8,10 +9,11 @@ use libcnb::{
 };
 use rand::Rng;
 
-// Set default environment values
+// Set default environment values, plus any contributed by the detected framework
 pub(crate) fn default_env(
     context: &BuildContext<RubyBuildpack>,
     platform_env: &Env,
+    framework: Option<&Framework>,
 ) -> libcnb::Result<(Env, Store), RubyBuildpackError> {
     // Get system env vars
     let mut env = Env::from_current();
@@ -24,22 +26,23 @@ pub(crate) fn default_env(
 
     let (default_secret_key_base, store) = fetch_secret_key_base_from_store(&context.store);
 
+    let mut defaults = vec![
+        ("SECRET_KEY_BASE", default_secret_key_base.as_str()),
+        ("JRUBY_OPTS", "-Xcompile.invokedynamic=false"),
+        ("RACK_ENV", "production"),
+        ("MALLOC_ARENA_MAX", "2"),
+        // Default processes pass `$(PORT)` to the server, the launcher leaves it
+        // unexpanded when the platform does not set `PORT` i.e. `docker run`
+        ("PORT", "5000"),
+    ];
+    if let Some(framework) = framework {
+        defaults.extend(framework.env());
+    }
+
     let env_defaults_layer = context //
         .handle_layer(
             layer_name!("env_defaults"),
-            DefaultEnvLayer::new(
-                [
-                    ("SECRET_KEY_BASE", default_secret_key_base.as_str()),
-                    ("JRUBY_OPTS", "-Xcompile.invokedynamic=false"),
-                    ("RACK_ENV", "production"),
-                    ("RAILS_ENV", "production"),
-                    ("RAILS_SERVE_STATIC_FILES", "enabled"),
-                    ("RAILS_LOG_TO_STDOUT", "enabled"),
-                    ("MALLOC_ARENA_MAX", "2"),
-                    ("DISABLE_SPRING", "1"),
-                ]
-                .into_iter(),
-            ),
+            DefaultEnvLayer::new(defaults.into_iter()),
         )?;
     env = env_defaults_layer.env.apply(Scope::Build, &env);
 
@@ -3,29 +3,58 @@ use commons::output::{
     section_log::{log_step, SectionLogger},
 };
 
+use crate::bundler_credentials::is_credential_key;
+use crate::command_timeout::TimedCommand;
+use crate::env_flag;
 use crate::gem_list::GemList;
-use crate::rake_status::{check_rake_ready, RakeStatus};
+use crate::layers::rake_detect_layer::{self, RakeDetectLayer, HEROKU_SKIP_RAKE_DIGEST};
+use crate::rake_status::{check_rake_ready, RakeStatus, ASSET_MANIFEST_GLOBS};
 use crate::rake_task_detect::RakeDetect;
+use crate::steps::RakeTasks;
 use crate::RubyBuildpack;
 use crate::RubyBuildpackError;
+use commons::metadata_digest::{DigestError, MetadataDigest};
 use libcnb::build::BuildContext;
+use libcnb::data::layer_name;
 use libcnb::Env;
+use std::path::{Path, PathBuf};
 
+/// Manifest globs to check before running `rake -P`, `None` when rake is not needed
+///
+/// A committed asset manifest only skips detection when no rake tasks are configured,
+/// configured tasks still need `rake -P`. Compiling assets is then skipped by
+/// `rake_assets_install` instead.
+pub(crate) fn rake_detect_globs(
+    assets_with_rake: bool,
+    rake_tasks: &RakeTasks,
+) -> Option<&'static [&'static str]> {
+    if !rake_tasks.tasks.is_empty() {
+        Some(&[])
+    } else if assets_with_rake {
+        Some(&ASSET_MANIFEST_GLOBS)
+    } else {
+        None
+    }
+}
+
+/// Detects rake tasks with `rake -P`, reusing results from the last build when unchanged
+///
+/// # Errors
+///
+/// Errors if the digest cannot be generated or `rake -P` fails.
 pub(crate) fn detect_rake_tasks(
     logger: &dyn SectionLogger,
     gem_list: &GemList,
     context: &BuildContext<RubyBuildpack>,
     env: &Env,
-) -> Result<Option<RakeDetect>, RubyBuildpackError> {
+    assets_dir: &Path,
+    manifest_globs: &[&str],
+) -> libcnb::Result<Option<RakeDetect>, RubyBuildpackError> {
     let rake = fmt::value("rake");
     let gemfile = fmt::value("Gemfile");
     let rakefile = fmt::value("Rakefile");
 
-    match check_rake_ready(
-        &context.app_dir,
-        gem_list,
-        [".sprockets-manifest-*.json", "manifest-*.json"],
-    ) {
+    match check_rake_ready(&context.app_dir, gem_list, assets_dir, manifest_globs) {
         RakeStatus::MissingRakeGem => {
             log_step(format!(
                 "Skipping rake tasks {}",
@@ -69,10 +98,76 @@ pub(crate) fn detect_rake_tasks(
                 path = fmt::value(path.to_string_lossy())
             ));
 
-            let rake_detect = RakeDetect::from_rake_command(logger, env, true)
-                .map_err(RubyBuildpackError::RakeDetectError)?;
+            let timeout = TimedCommand::RakeDetect.timeout(env)?;
+            if env_flag::enabled(env, HEROKU_SKIP_RAKE_DIGEST) {
+                log_step(format!(
+                    "Skipping rake task cache {}",
+                    fmt::details(format!("found {HEROKU_SKIP_RAKE_DIGEST}"))
+                ));
+
+                return Ok(Some(rake_detect_layer::rake_detect(logger, env, timeout)?));
+            }
+
+            let rake_detect_layer = context.handle_layer(
+                layer_name!("rake_detect"),
+                RakeDetectLayer {
+                    env,
+                    timeout,
+                    digest: MetadataDigest::new_env_files_ignoring(
+                        &context.platform,
+                        &rake_detect_layer::digest_paths(&context.app_dir, &path)
+                            .iter()
+                            .map(PathBuf::as_path)
+                            .collect::<Vec<_>>(),
+                        is_credential_key,
+                    )
+                    .map_err(|error| match error {
+                        DigestError::CannotReadFile(path, error) => {
+                            RubyBuildpackError::RakeDetectDigestError(path, error)
+                        }
+                    })?,
+                    section_log: logger,
+                },
+            )?;
+            let rake_detect = rake_detect_layer.content_metadata.metadata.rake_detect;
 
             Ok(Some(rake_detect))
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::str::FromStr;
+
+    #[test]
+    fn test_rake_detect_with_committed_manifest() {
+        let tmp = tempfile::tempdir().unwrap();
+        let app_dir = tmp.path();
+        let assets_dir = app_dir.join("public").join("assets");
+        fs_err::write(app_dir.join("Rakefile"), "").unwrap();
+        fs_err::create_dir_all(&assets_dir).unwrap();
+        fs_err::write(assets_dir.join("manifest-abc123.json"), "{}").unwrap();
+        let gem_list =
+            GemList::from_str("Gems included by the bundle:\n  * rake (13.0.6)\n").unwrap();
+
+        let no_tasks = RakeTasks::default();
+        let globs = rake_detect_globs(true, &no_tasks).unwrap();
+        assert!(matches!(
+            check_rake_ready(app_dir, &gem_list, &assets_dir, globs),
+            RakeStatus::SkipManifestFound(_)
+        ));
+        assert_eq!(None, rake_detect_globs(false, &no_tasks));
+
+        let rake_tasks = RakeTasks {
+            tasks: vec![String::from("sitemap:generate")],
+            replace_assets: false,
+        };
+        let globs = rake_detect_globs(true, &rake_tasks).unwrap();
+        assert!(matches!(
+            check_rake_ready(app_dir, &gem_list, &assets_dir, globs),
+            RakeStatus::Ready(_)
+        ));
+    }
+}
@@ -1,51 +1,184 @@
+use crate::framework::Framework;
 use crate::gem_list::GemList;
+use crate::procfile::Procfile;
 use crate::RubyBuildpack;
+use crate::RubyBuildpackError;
 use commons::output::{
     fmt,
     section_log::{log_step, SectionLogger},
 };
+use core::str::FromStr;
 use libcnb::build::BuildContext;
 use libcnb::data::launch::Process;
 use libcnb::data::launch::ProcessBuilder;
+use libcnb::data::launch::ProcessType;
 use libcnb::data::process_type;
+use std::os::unix::fs::PermissionsExt;
 use std::path::Path;
 
+/// Launch processes from the `Procfile` when present, otherwise a detected `web` process
+///
+/// A `worker` process is added for a detected background job library unless the
+/// `Procfile` already defines one.
+///
+/// # Errors
+///
+/// Errors if the `Procfile` cannot be read or contains a malformed line.
 pub(crate) fn get_default_process(
+    logger: &dyn SectionLogger,
+    context: &BuildContext<RubyBuildpack>,
+    gem_list: &GemList,
+    framework: Option<&Framework>,
+) -> Result<Vec<Process>, RubyBuildpackError> {
+    let path = context.app_dir.join("Procfile");
+    let mut processes = if path.exists() {
+        let contents = fs_err::read_to_string(&path)
+            .map_err(|error| RubyBuildpackError::ProcfileReadError(path.clone(), error))?;
+        let procfile =
+            Procfile::from_str(&contents).map_err(RubyBuildpackError::ProcfileParseError)?;
+
+        let procfile_name = fmt::value("Procfile");
+        for (name, command) in &procfile.entries {
+            log_step(format!(
+                "Detected {} process from {procfile_name} {}",
+                fmt::value(name.to_string()),
+                fmt::details(fmt::command(command))
+            ));
+        }
+        if procfile.entries.is_empty() {
+            log_step(format!(
+                "Skipping default processes (empty {procfile_name})"
+            ));
+        }
+
+        procfile.processes()
+    } else {
+        detected_web_process(logger, context, gem_list, framework)
+            .into_iter()
+            .collect()
+    };
+
+    if let Some(worker) = detect_worker(gem_list, &context.app_dir) {
+        let gem = fmt::value(worker.gem());
+        if processes
+            .iter()
+            .any(|process| process.r#type == process_type!("worker"))
+        {
+            log_step(format!(
+                "Skipping default worker process ({gem} gem found but {} already defined in {})",
+                fmt::value("worker"),
+                fmt::value("Procfile")
+            ));
+        } else {
+            let command = worker.command();
+            log_step(format!(
+                "Detected {} process ({gem} gem found) {}",
+                fmt::value("worker"),
+                fmt::details(fmt::command(command.join(" ")))
+            ));
+            processes.push(direct_process(process_type!("worker"), &command));
+        }
+    }
+
+    for process in convenience_processes(gem_list, &context.app_dir) {
+        if processes
+            .iter()
+            .any(|existing| existing.r#type == process.r#type)
+        {
+            continue;
+        }
+
+        log_step(format!(
+            "Detected {} process {}",
+            fmt::value(process.r#type.to_string()),
+            fmt::details(fmt::command(
+                process
+                    .command
+                    .iter()
+                    .chain(&process.args)
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ))
+        ));
+        processes.push(process);
+    }
+
+    Ok(processes)
+}
+
+fn detected_web_process(
     _logger: &dyn SectionLogger,
     context: &BuildContext<RubyBuildpack>,
     gem_list: &GemList,
+    framework: Option<&Framework>,
 ) -> Option<Process> {
     let config_ru = fmt::value("config.ru");
     let rails = fmt::value("rails");
     let rack = fmt::value("rack");
     let railties = fmt::value("railties");
-    match detect_web(gem_list, &context.app_dir) {
+    let app_server = detect_app_server(gem_list, &context.app_dir);
+    let process = match detect_web(gem_list, &context.app_dir, app_server) {
         WebProcess::Rails => {
-            log_step(format!("Detected rails app ({rails} gem found)"));
+            let version = gem_list
+                .version_for("railties")
+                .map(ToString::to_string)
+                .unwrap_or_default();
+            if version.is_empty() {
+                log_step(format!("Detected rails app ({rails} gem found)"));
+            } else {
+                log_step(format!(
+                    "Detected rails app ({railties} {} found)",
+                    fmt::value(&version)
+                ));
+            }
 
-            Some(default_rails())
+            web_process(&Framework::Rails { version }.web_command())
         }
         WebProcess::RackWithConfigRU => {
+            let gem = match (framework, app_server) {
+                (Some(Framework::Rails { .. }) | None, Some(app_server)) => app_server.gem(),
+                (Some(framework), _) => framework.gem(),
+                (None, None) => "rack",
+            };
             log_step(format!(
-                "Detected rack app ({rack} gem found and {config_ru} at root of application)"
+                "Detected rack app ({} gem found and {config_ru} at root of application)",
+                fmt::value(gem)
             ));
 
-            Some(default_rack())
+            web_process(&Framework::Rack.web_command())
         }
         WebProcess::RackMissingConfigRu => {
             log_step(format!(
                 "Skipping default web process ({rack} gem found but missing {config_ru} file)"
             ));
 
-            None
+            return None;
         }
         WebProcess::Missing => {
             log_step(format!(
                 "Skipping default web process ({rails}, {railties}, and {rack} not found)"
             ));
 
-            None
+            return None;
         }
+    };
+
+    if let Some(app_server) = app_server {
+        let command = app_server.command();
+        let reason = match app_server.config_file() {
+            Some(path) => format!("{} found", fmt::value(path)),
+            None => format!("{} gem found", fmt::value(app_server.gem())),
+        };
+        log_step(format!(
+            "Detected {} web server ({reason}) {}",
+            fmt::value(app_server.gem()),
+            fmt::details(fmt::command(command.join(" ")))
+        ));
+
+        Some(web_process(&command))
+    } else {
+        Some(process)
     }
 }
 
@@ -56,10 +189,10 @@ enum WebProcess {
     Missing,
 }
 
-fn detect_web(gem_list: &GemList, app_path: &Path) -> WebProcess {
+fn detect_web(gem_list: &GemList, app_path: &Path, app_server: Option<AppServer>) -> WebProcess {
     if gem_list.has("railties") {
         WebProcess::Rails
-    } else if gem_list.has("rack") {
+    } else if gem_list.has("rack") || app_server.is_some() {
         if app_path.join("config.ru").exists() {
             WebProcess::RackWithConfigRU
         } else {
@@ -70,32 +203,371 @@ fn detect_web(gem_list: &GemList, app_path: &Path) -> WebProcess {
     }
 }
 
-fn default_rack() -> Process {
-    ProcessBuilder::new(process_type!("web"), ["bash"])
-        .args([
-            "-c",
-            &[
-                "bundle exec rackup",
-                "--port \"$PORT\"",
-                "--host \"0.0.0.0\"",
-            ]
-            .join(" "),
-        ])
-        .default(true)
-        .build()
+/// Web servers, in order of precedence when more than one is found
+///
+/// When the server's config file exists it's passed to the server as-is instead of
+/// overriding its settings with CLI flags, the config is expected to bind to `$PORT`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AppServer {
+    Puma { config_file: bool },
+    Unicorn { config_file: bool },
+    Falcon { config_file: bool },
+    Thin { config_file: bool },
+    Passenger { config_file: bool },
+}
+
+impl AppServer {
+    fn gem(self) -> &'static str {
+        match self {
+            AppServer::Puma { .. } => "puma",
+            AppServer::Unicorn { .. } => "unicorn",
+            AppServer::Falcon { .. } => "falcon",
+            AppServer::Thin { .. } => "thin",
+            AppServer::Passenger { .. } => "passenger",
+        }
+    }
+
+    /// Path of the server's config file relative to the app root
+    fn config_path(self) -> &'static str {
+        match self {
+            AppServer::Puma { .. } => "config/puma.rb",
+            AppServer::Unicorn { .. } => "config/unicorn.rb",
+            AppServer::Falcon { .. } => "falcon.rb",
+            AppServer::Thin { .. } => "config/thin.yml",
+            AppServer::Passenger { .. } => "Passengerfile.json",
+        }
+    }
+
+    fn config_file(self) -> Option<&'static str> {
+        match self {
+            AppServer::Puma { config_file }
+            | AppServer::Unicorn { config_file }
+            | AppServer::Falcon { config_file }
+            | AppServer::Thin { config_file }
+            | AppServer::Passenger { config_file } => {
+                if config_file {
+                    Some(self.config_path())
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// Command run directly, without a shell, so the server receives signals such as
+    /// `SIGTERM`. The launcher expands `$(PORT)` in arguments.
+    fn command(self) -> Vec<&'static str> {
+        match self {
+            AppServer::Puma { config_file: true } => {
+                vec!["bundle", "exec", "puma", "--config", "config/puma.rb"]
+            }
+            AppServer::Puma { config_file: false } => {
+                vec!["bundle", "exec", "puma", "--port", "$(PORT)"]
+            }
+            AppServer::Unicorn { config_file: true } => {
+                vec![
+                    "bundle",
+                    "exec",
+                    "unicorn",
+                    "--config-file",
+                    "config/unicorn.rb",
+                ]
+            }
+            AppServer::Unicorn { config_file: false } => {
+                vec!["bundle", "exec", "unicorn", "--port", "$(PORT)"]
+            }
+            AppServer::Falcon { config_file: true } => vec!["bundle", "exec", "falcon", "host"],
+            AppServer::Falcon { config_file: false } => vec![
+                "bundle",
+                "exec",
+                "falcon",
+                "serve",
+                "--bind",
+                "http://0.0.0.0:$(PORT)",
+            ],
+            AppServer::Thin { config_file: true } => {
+                vec![
+                    "bundle",
+                    "exec",
+                    "thin",
+                    "start",
+                    "--config",
+                    "config/thin.yml",
+                ]
+            }
+            AppServer::Thin { config_file: false } => vec![
+                "bundle",
+                "exec",
+                "thin",
+                "start",
+                "--port",
+                "$(PORT)",
+                "--address",
+                "0.0.0.0",
+            ],
+            AppServer::Passenger { config_file: true } => {
+                vec!["bundle", "exec", "passenger", "start"]
+            }
+            AppServer::Passenger { config_file: false } => vec![
+                "bundle",
+                "exec",
+                "passenger",
+                "start",
+                "--port",
+                "$(PORT)",
+                "--address",
+                "0.0.0.0",
+            ],
+        }
+    }
+}
+
+fn detect_app_server(gem_list: &GemList, app_path: &Path) -> Option<AppServer> {
+    let servers: [fn(bool) -> AppServer; 5] = [
+        |config_file| AppServer::Puma { config_file },
+        |config_file| AppServer::Unicorn { config_file },
+        |config_file| AppServer::Falcon { config_file },
+        |config_file| AppServer::Thin { config_file },
+        |config_file| AppServer::Passenger { config_file },
+    ];
+
+    servers.iter().find_map(|server| {
+        let gem = server(false).gem();
+        let config_file = app_path.join(server(false).config_path()).exists();
+        gem_list.has(gem).then(|| server(config_file))
+    })
+}
+
+/// Background job libraries, in order of precedence when more than one is found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Worker {
+    Sidekiq { config_file: bool },
+    GoodJob,
+    SolidQueue { bin_jobs: bool },
+    Resque,
+    DelayedJob,
+    Que,
 }
 
-fn default_rails() -> Process {
-    ProcessBuilder::new(process_type!("web"), ["bash"])
-        .args([
-            "-c",
-            &[
-                "bin/rails server",
-                "--port \"$PORT\"",
-                "--environment \"$RAILS_ENV\"",
-            ]
-            .join(" "),
-        ])
-        .default(true)
+impl Worker {
+    fn gem(self) -> &'static str {
+        match self {
+            Worker::Sidekiq { .. } => "sidekiq",
+            Worker::GoodJob => "good_job",
+            Worker::SolidQueue { .. } => "solid_queue",
+            Worker::Resque => "resque",
+            Worker::DelayedJob => "delayed_job",
+            Worker::Que => "que",
+        }
+    }
+
+    /// Command run directly without a shell, except for `resque` which needs one to
+    /// default `QUEUE`
+    fn command(self) -> Vec<&'static str> {
+        match self {
+            Worker::Sidekiq { config_file: true } => {
+                vec!["bundle", "exec", "sidekiq", "-C", "config/sidekiq.yml"]
+            }
+            Worker::Sidekiq { config_file: false } => vec!["bundle", "exec", "sidekiq"],
+            Worker::GoodJob => vec!["bundle", "exec", "good_job", "start"],
+            Worker::SolidQueue { bin_jobs: true } => vec!["bin/jobs"],
+            Worker::SolidQueue { bin_jobs: false } => {
+                vec!["bundle", "exec", "rake", "solid_queue:start"]
+            }
+            Worker::Resque => vec![
+                "bash",
+                "-c",
+                "QUEUE=\"${QUEUE:-*}\" exec bundle exec rake resque:work",
+            ],
+            Worker::DelayedJob => vec!["bundle", "exec", "rake", "jobs:work"],
+            Worker::Que => vec!["bundle", "exec", "que"],
+        }
+    }
+}
+
+fn detect_worker(gem_list: &GemList, app_path: &Path) -> Option<Worker> {
+    if gem_list.has("sidekiq") {
+        Some(Worker::Sidekiq {
+            config_file: app_path.join("config").join("sidekiq.yml").exists(),
+        })
+    } else if gem_list.has("good_job") {
+        Some(Worker::GoodJob)
+    } else if gem_list.has("solid_queue") {
+        Some(Worker::SolidQueue {
+            bin_jobs: app_path.join("bin").join("jobs").exists(),
+        })
+    } else if gem_list.has("resque") {
+        Some(Worker::Resque)
+    } else if gem_list.has("delayed_job") {
+        Some(Worker::DelayedJob)
+    } else if gem_list.has("que") {
+        Some(Worker::Que)
+    } else {
+        None
+    }
+}
+
+/// Non-default `console` and `rake` processes, i.e. `docker run --entrypoint console <image>`
+///
+/// These run without `bash -c` so arguments are passed through to the command.
+/// Executable binstubs in `bin/` are preferred over `bundle exec`.
+fn convenience_processes(gem_list: &GemList, app_path: &Path) -> Vec<Process> {
+    let mut processes = Vec::new();
+
+    let console = if gem_list.has("railties") {
+        if is_executable(&app_path.join("bin").join("rails")) {
+            vec!["bin/rails", "console"]
+        } else {
+            vec!["bundle", "exec", "rails", "console"]
+        }
+    } else if is_executable(&app_path.join("bin").join("console")) {
+        vec!["bin/console"]
+    } else {
+        vec!["bundle", "exec", "irb"]
+    };
+    processes.push(direct_process(process_type!("console"), &console));
+
+    if gem_list.has("rake") {
+        let rake = if is_executable(&app_path.join("bin").join("rake")) {
+            vec!["bin/rake"]
+        } else {
+            vec!["bundle", "exec", "rake"]
+        };
+        processes.push(direct_process(process_type!("rake"), &rake));
+    }
+
+    processes
+}
+
+fn direct_process(process_type: ProcessType, command: &[&str]) -> Process {
+    ProcessBuilder::new(process_type, [command[0]])
+        .args(command[1..].iter().copied())
         .build()
 }
+
+fn is_executable(path: &Path) -> bool {
+    match fs_err::metadata(path) {
+        Ok(metadata) => metadata.is_file() && metadata.permissions().mode() & 0o111 != 0,
+        Err(_) => false,
+    }
+}
+
+fn web_process(command: &[&str]) -> Process {
+    let mut process = direct_process(process_type!("web"), command);
+    process.default = true;
+    process
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn gem_list(entries: &str) -> GemList {
+        GemList::from_str(&format!("Gems included by the bundle:\n{entries}")).unwrap()
+    }
+
+    #[test]
+    fn test_detect_app_server() {
+        let barnes_app = Path::new("tests/fixtures/barnes_app");
+        let puma = gem_list("  * barnes (0.0.9)\n  * puma (6.3.0)\n");
+
+        let app_server = detect_app_server(&puma, barnes_app).unwrap();
+        assert_eq!(AppServer::Puma { config_file: true }, app_server);
+        assert_eq!(Some("config/puma.rb"), app_server.config_file());
+        assert_eq!(
+            "bundle exec puma --config config/puma.rb",
+            app_server.command().join(" ")
+        );
+        assert!(matches!(
+            detect_web(&puma, barnes_app, Some(app_server)),
+            WebProcess::RackWithConfigRU
+        ));
+
+        let tmp = tempfile::tempdir().unwrap();
+        let unicorn = gem_list("  * unicorn (6.1.0)\n  * thin (1.8.2)\n");
+        let app_server = detect_app_server(&unicorn, tmp.path()).unwrap();
+        assert_eq!(AppServer::Unicorn { config_file: false }, app_server);
+        assert_eq!(None, app_server.config_file());
+        assert_eq!(
+            vec!["bundle", "exec", "unicorn", "--port", "$(PORT)"],
+            app_server.command()
+        );
+
+        let process = web_process(&app_server.command());
+        assert!(process.default);
+        assert_eq!(vec!["bundle"], process.command);
+        assert_eq!(vec!["exec", "unicorn", "--port", "$(PORT)"], process.args);
+        assert!(matches!(
+            detect_web(&unicorn, tmp.path(), Some(app_server)),
+            WebProcess::RackMissingConfigRu
+        ));
+
+        assert_eq!(
+            None,
+            detect_app_server(&gem_list("  * rack (3.0.0)\n"), tmp.path())
+        );
+    }
+
+    #[test]
+    fn test_convenience_processes() {
+        let tmp = tempfile::tempdir().unwrap();
+        let app_dir = tmp.path();
+
+        let processes = convenience_processes(&gem_list("  * rack (3.0.0)\n"), app_dir);
+        assert_eq!(1, processes.len());
+        assert_eq!(process_type!("console"), processes[0].r#type);
+        assert_eq!(vec!["bundle"], processes[0].command);
+        assert_eq!(vec!["exec", "irb"], processes[0].args);
+        assert!(!processes[0].default);
+
+        let rails = gem_list("  * railties (7.1.2)\n  * rake (13.0.6)\n");
+        fs_err::create_dir_all(app_dir.join("bin")).unwrap();
+        fs_err::write(app_dir.join("bin").join("rake"), "").unwrap();
+        fs_err::write(app_dir.join("bin").join("rails"), "").unwrap();
+        fs_err::set_permissions(
+            app_dir.join("bin").join("rails"),
+            std::fs::Permissions::from_mode(0o755),
+        )
+        .unwrap();
+
+        let processes = convenience_processes(&rails, app_dir);
+        assert_eq!(vec!["bin/rails"], processes[0].command);
+        assert_eq!(vec!["console"], processes[0].args);
+        assert_eq!(process_type!("rake"), processes[1].r#type);
+        assert_eq!(vec!["bundle"], processes[1].command);
+        assert_eq!(vec!["exec", "rake"], processes[1].args);
+    }
+
+    #[test]
+    fn test_detect_worker() {
+        let tmp = tempfile::tempdir().unwrap();
+        let app_dir = tmp.path();
+
+        assert_eq!(
+            None,
+            detect_worker(&gem_list("  * rack (3.0.0)\n"), app_dir)
+        );
+        assert_eq!(
+            Some(Worker::GoodJob),
+            detect_worker(
+                &gem_list("  * good_job (3.21.0)\n  * que (2.2.0)\n"),
+                app_dir
+            )
+        );
+
+        let sidekiq = gem_list("  * sidekiq (7.2.0)\n  * resque (2.6.0)\n");
+        let worker = detect_worker(&sidekiq, app_dir).unwrap();
+        assert_eq!(Worker::Sidekiq { config_file: false }, worker);
+        assert_eq!(vec!["bundle", "exec", "sidekiq"], worker.command());
+
+        fs_err::create_dir_all(app_dir.join("config")).unwrap();
+        fs_err::write(app_dir.join("config").join("sidekiq.yml"), "").unwrap();
+        assert_eq!(
+            "bundle exec sidekiq -C config/sidekiq.yml",
+            detect_worker(&sidekiq, app_dir)
+                .unwrap()
+                .command()
+                .join(" ")
+        );
+    }
+}
@@ -0,0 +1,281 @@
+use crate::bundler_credentials::Redact;
+use crate::command_timeout::{self, TimedCommand};
+use crate::env_flag;
+use crate::RubyBuildpack;
+use crate::RubyBuildpackError;
+use commons::gemfile_lock::GemfileLock;
+use commons::output::{
+    fmt::{self, HELP},
+    section_log::{log_step, log_step_timed, log_warning_later, SectionLogger},
+};
+use core::str::FromStr;
+use fun_run::{self, CmdError, CommandWithName};
+use libcnb::build::BuildContext;
+use libcnb::Env;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::process::Command;
+
+/// Opt-in to running `bundle lock --add-platform` instead of failing the build
+pub(crate) const HEROKU_BUNDLE_LOCK_ADD_PLATFORM: &str = "HEROKU_BUNDLE_LOCK_ADD_PLATFORM";
+
+/// Whether the `Gemfile.lock` changed on disk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum LockfileChanged {
+    Yes,
+    No,
+}
+
+/// Ensure the `Gemfile.lock` can install gems for the platform we're building on
+///
+/// Lockfiles generated on macOS often lack i.e. `x86_64-linux` in `PLATFORMS`. With
+/// `BUNDLE_DEPLOYMENT=1` this fails the install, or bundler picks the wrong native
+/// variants of gems such as `nokogiri`. When the platform is missing either:
+///
+/// - Run `bundle lock --add-platform` against a scratch copy of the lockfile and use it,
+///   when `HEROKU_BUNDLE_LOCK_ADD_PLATFORM=1`
+/// - Fail with the command the user needs to run locally (default)
+///
+/// When the lockfile only includes the generic `ruby` platform gems still install
+/// (compiling native extensions from source) so a warning is emitted instead. `JRuby`
+/// lockfiles list `java`, which installs the same gems on every architecture.
+pub(crate) fn lockfile_platform(
+    _logger: &dyn SectionLogger,
+    context: &BuildContext<RubyBuildpack>,
+    env: &Env,
+    gemfile_lock: &GemfileLock,
+) -> Result<LockfileChanged, RubyBuildpackError> {
+    let platform = build_platform();
+    let add_platform_cmd = fmt::command(format!("bundle lock --add-platform {platform}"));
+    let gemfile_lock_name = fmt::value("Gemfile.lock");
+
+    match platform_state(&gemfile_lock.platforms, &platform) {
+        PlatformState::Present => Ok(LockfileChanged::No),
+        PlatformState::RubyOnly => {
+            log_warning_later(format!(
+                "Missing platform {platform} in {gemfile_lock_name}\n\n\
+                Gems with native extensions will be compiled from source instead of using\n\
+                precompiled versions. To use precompiled gems run:\n\n\
+                {add_platform_cmd}\n\n\
+                Then commit the results to git and push again.",
+                platform = fmt::value(&platform)
+            ));
+            Ok(LockfileChanged::No)
+        }
+        PlatformState::Missing => {
+            if env_flag::enabled(env, HEROKU_BUNDLE_LOCK_ADD_PLATFORM) {
+                log_step(format!(
+                    "Adding platform {} to {gemfile_lock_name} {}",
+                    fmt::value(&platform),
+                    fmt::details(format!("{HEROKU_BUNDLE_LOCK_ADD_PLATFORM} found"))
+                ));
+
+                add_platform(env, &context.app_dir, &platform, gemfile_lock)?;
+
+                log_step(format!(
+                    "{HELP} Avoid this step by running {add_platform_cmd} locally and committing the results"
+                ));
+                Ok(LockfileChanged::Yes)
+            } else {
+                Err(RubyBuildpackError::LockfileMissingPlatform(platform))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PlatformState {
+    Present,
+    RubyOnly,
+    Missing,
+}
+
+/// Bundler platform name of the system being built on i.e. `x86_64-linux`
+pub(crate) fn build_platform() -> String {
+    linux_platform(std::env::consts::ARCH)
+}
+
+/// Bundler platform name for the given CPU architecture
+fn linux_platform(arch: &str) -> String {
+    format!("{arch}-linux")
+}
+
+/// Whether a lockfile or gem platform installs on the build platform
+///
+/// The `gnu` suffix is the same glibc platform, other suffixes such as `musl` are not.
+pub(crate) fn satisfies_platform(platform: &str, linux_platform: &str) -> bool {
+    matches!(platform.strip_prefix(linux_platform), Some("" | "-gnu"))
+}
+
+fn platform_state(platforms: &[String], linux_platform: &str) -> PlatformState {
+    if platforms
+        .iter()
+        .any(|platform| platform == "java" || satisfies_platform(platform, linux_platform))
+    {
+        PlatformState::Present
+    } else if platforms.iter().any(|platform| platform == "ruby") {
+        PlatformState::RubyOnly
+    } else {
+        PlatformState::Missing
+    }
+}
+
+/// Runs `bundle lock --add-platform` against a scratch copy of the lockfile
+///
+/// The app's `Gemfile.lock` is only replaced when the command succeeds and every
+/// locked gem keeps its version.
+fn add_platform(
+    env: &Env,
+    app_dir: &Path,
+    platform: &str,
+    gemfile_lock: &GemfileLock,
+) -> Result<(), RubyBuildpackError> {
+    let path_env = env.get("PATH").cloned();
+    let timeout = TimedCommand::BundleLock.timeout(env)?;
+    let lockfile = app_dir.join("Gemfile.lock");
+    let mut cmd = Command::new("bundle");
+    cmd.args(["lock", "--add-platform", platform, "--lockfile"]);
+    let mut cmd = cmd.named(format!("bundle lock --add-platform {platform}"));
+    let system_error = |error| {
+        RubyBuildpackError::LockfileAddPlatformError(CmdError::SystemError(
+            String::from("bundle lock --add-platform"),
+            error,
+        ))
+    };
+
+    let scratch = tempfile::tempdir().map_err(system_error)?;
+    let scratch_lockfile = scratch.path().join("Gemfile.lock");
+    fs_err::copy(&lockfile, &scratch_lockfile).map_err(system_error)?;
+
+    cmd.mut_cmd()
+        .arg(&scratch_lockfile)
+        .env_clear()
+        .envs(env)
+        .env("BUNDLE_GEMFILE", app_dir.join("Gemfile"))
+        .env("BUNDLE_FROZEN", "false")
+        .env("BUNDLE_DEPLOYMENT", "false");
+
+    log_step_timed(format!("Running {}", fmt::command(cmd.name())), || {
+        command_timeout::named_output(&mut cmd, timeout)
+    })
+    .map_err(|error| {
+        error.map_cmd(|error| fun_run::map_which_problem(error, cmd.mut_cmd(), path_env))
+    })
+    .map_err(|error| error.redact(&Redact::from_env(env)))
+    .map_err(|error| error.into_buildpack_error(RubyBuildpackError::LockfileAddPlatformError))?;
+
+    let contents = fs_err::read_to_string(&scratch_lockfile).map_err(system_error)?;
+    let changed = changed_versions(
+        gemfile_lock,
+        &GemfileLock::from_str(&contents).expect("Infallible"),
+    );
+    if !changed.is_empty() {
+        return Err(RubyBuildpackError::LockfileAddPlatformChangedVersions(
+            changed,
+        ));
+    }
+
+    fs_err::copy(&scratch_lockfile, &lockfile).map_err(system_error)?;
+
+    Ok(())
+}
+
+/// Gems whose locked versions differ, i.e. `rack (3.0.8 to 3.0.9)`
+///
+/// Adding a platform may add platform specific variants of a gem, but should not
+/// change the version of any gem.
+fn changed_versions(before: &GemfileLock, after: &GemfileLock) -> Vec<String> {
+    let versions = |gemfile_lock: &GemfileLock| {
+        let mut versions = BTreeMap::<String, BTreeSet<String>>::new();
+        for spec in &gemfile_lock.specs {
+            versions
+                .entry(spec.name.clone())
+                .or_default()
+                .insert(spec.version.clone());
+        }
+        versions
+    };
+    let before = versions(before);
+    let after = versions(after);
+
+    before
+        .keys()
+        .chain(after.keys())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .filter_map(|name| {
+            let join = |list: Option<&BTreeSet<String>>| match list {
+                Some(list) => list.iter().cloned().collect::<Vec<_>>().join(", "),
+                None => String::from("none"),
+            };
+            let (old, now) = (before.get(name), after.get(name));
+
+            (old != now).then(|| format!("{name} ({} to {})", join(old), join(now)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_platform_state() {
+        let platforms = |list: &[&str]| list.iter().map(|s| (*s).to_string()).collect::<Vec<_>>();
+        let linux = linux_platform("x86_64");
+
+        assert_eq!(
+            platform_state(&platforms(&["ruby", "x86_64-linux"]), &linux),
+            PlatformState::Present
+        );
+        assert_eq!(
+            platform_state(&platforms(&["x86_64-linux-gnu"]), &linux),
+            PlatformState::Present
+        );
+        assert_eq!(
+            platform_state(&platforms(&["ruby", "x86_64-linux-musl"]), &linux),
+            PlatformState::RubyOnly
+        );
+        assert_eq!(
+            platform_state(&platforms(&["ruby", "arm64-darwin-22"]), &linux),
+            PlatformState::RubyOnly
+        );
+        assert_eq!(
+            platform_state(&platforms(&["arm64-darwin-22", "aarch64-linux"]), &linux),
+            PlatformState::Missing
+        );
+        assert_eq!(
+            platform_state(
+                &platforms(&["arm64-darwin-22", "aarch64-linux"]),
+                &linux_platform("aarch64")
+            ),
+            PlatformState::Present
+        );
+        assert_eq!(
+            platform_state(&platforms(&["java"]), &linux),
+            PlatformState::Present
+        );
+    }
+
+    #[test]
+    fn test_changed_versions() {
+        let before = GemfileLock::from_str(
+            "GEM\n  remote: https://rubygems.org/\n  specs:\n    nokogiri (1.15.4-arm64-darwin)\n    rack (3.0.8)\n\nPLATFORMS\n  arm64-darwin-22\n",
+        )
+        .unwrap();
+        let after = GemfileLock::from_str(
+            "GEM\n  remote: https://rubygems.org/\n  specs:\n    nokogiri (1.15.4-arm64-darwin)\n    nokogiri (1.15.4-x86_64-linux)\n    rack (3.0.8)\n\nPLATFORMS\n  arm64-darwin-22\n  x86_64-linux\n",
+        )
+        .unwrap();
+        assert!(changed_versions(&before, &after).is_empty());
+
+        let after = GemfileLock::from_str(
+            "GEM\n  remote: https://rubygems.org/\n  specs:\n    nokogiri (1.16.0-x86_64-linux)\n    rack (3.0.8)\n",
+        )
+        .unwrap();
+        assert_eq!(
+            vec![String::from("nokogiri (1.15.4 to 1.16.0)")],
+            changed_versions(&before, &after)
+        );
+    }
+}
@@ -0,0 +1,152 @@
+use crate::command_timeout::{self, TimedCommand};
+use crate::env_flag;
+use crate::project_config::ProjectConfig;
+use crate::rake_task_detect::RakeDetect;
+use crate::RubyBuildpackError;
+use commons::output::{fmt, section_log::log_step_stream};
+use fun_run::{self, CommandWithName};
+use libcnb::Env;
+use std::process::Command;
+
+/// Space or comma separated rake tasks to run at build, overrides `project.toml`
+pub(crate) const HEROKU_RAKE_TASKS: &str = "HEROKU_RAKE_TASKS";
+
+/// Run the configured rake tasks instead of `assets:precompile`
+pub(crate) const HEROKU_RAKE_TASKS_REPLACE_ASSETS: &str = "HEROKU_RAKE_TASKS_REPLACE_ASSETS";
+
+/// Build time rake tasks configured by the app
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct RakeTasks {
+    pub(crate) tasks: Vec<String>,
+    pub(crate) replace_assets: bool,
+}
+
+impl RakeTasks {
+    /// Tasks from `HEROKU_RAKE_TASKS` when set, otherwise from `project.toml`
+    pub(crate) fn new(project_config: &ProjectConfig, env: &Env) -> Self {
+        let tasks = match env.get(HEROKU_RAKE_TASKS) {
+            Some(value) => value
+                .to_string_lossy()
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|task| !task.is_empty())
+                .map(String::from)
+                .collect(),
+            None => project_config.rake_tasks.clone(),
+        };
+
+        RakeTasks {
+            tasks,
+            replace_assets: env_flag::enabled(env, HEROKU_RAKE_TASKS_REPLACE_ASSETS)
+                || project_config.rake_tasks_replace_assets,
+        }
+    }
+}
+
+/// Runs each configured rake task in order
+///
+/// All tasks are checked against `rake -P` before any of them run. `rake_detect` is
+/// `None` when the app does not have the `rake` gem or a `Rakefile`.
+///
+/// # Errors
+///
+/// Errors if rake is not available, or a task is not defined by the app or fails.
+pub(crate) fn run_rake_tasks(
+    env: &Env,
+    rake_detect: Option<&RakeDetect>,
+    rake_tasks: &RakeTasks,
+) -> Result<(), RubyBuildpackError> {
+    let Some(rake_detect) = rake_detect else {
+        return Err(RubyBuildpackError::RakeTasksWithoutRake(
+            rake_tasks.tasks.clone(),
+        ));
+    };
+    if let Some(task) = missing_task(rake_detect, &rake_tasks.tasks) {
+        return Err(RubyBuildpackError::RakeTaskNotFound(task.to_string()));
+    }
+
+    let timeout = TimedCommand::RakeTasks.timeout(env)?;
+    for task in &rake_tasks.tasks {
+        let path_env = env.get("PATH").cloned();
+        let mut cmd = Command::new("bundle");
+        cmd.args(["exec", "rake", task, "--trace"])
+            .env_clear()
+            .envs(env);
+
+        log_step_stream(format!("Running {}", fmt::command(cmd.name())), |stream| {
+            command_timeout::stream_output(&mut cmd, stream.io(), stream.io(), timeout)
+        })
+        .map_err(|error| {
+            error.map_cmd(|error| fun_run::map_which_problem(error, &mut cmd, path_env))
+        })
+        .map_err(|error| {
+            error.into_buildpack_error(|error| {
+                RubyBuildpackError::RakeTaskFailed(task.clone(), error)
+            })
+        })?;
+    }
+
+    Ok(())
+}
+
+/// First task not listed by `rake -P`
+fn missing_task<'a>(rake_detect: &RakeDetect, tasks: &'a [String]) -> Option<&'a str> {
+    tasks
+        .iter()
+        .find(|task| !rake_detect.has_task(task))
+        .map(String::as_str)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::str::FromStr;
+
+    #[test]
+    fn test_rake_tasks_config() {
+        let project_config = ProjectConfig {
+            rake_tasks: vec![String::from("bootsnap:precompile")],
+            rake_tasks_replace_assets: false,
+            ..ProjectConfig::default()
+        };
+
+        let mut env = Env::new();
+        assert_eq!(
+            RakeTasks {
+                tasks: vec![String::from("bootsnap:precompile")],
+                replace_assets: false
+            },
+            RakeTasks::new(&project_config, &env)
+        );
+
+        env.insert(HEROKU_RAKE_TASKS, "sitemap:generate, i18n:js:export");
+        env.insert(HEROKU_RAKE_TASKS_REPLACE_ASSETS, "0");
+        assert!(!RakeTasks::new(&project_config, &env).replace_assets);
+
+        env.insert(HEROKU_RAKE_TASKS_REPLACE_ASSETS, "1");
+        assert_eq!(
+            RakeTasks {
+                tasks: vec![
+                    String::from("sitemap:generate"),
+                    String::from("i18n:js:export")
+                ],
+                replace_assets: true
+            },
+            RakeTasks::new(&project_config, &env)
+        );
+    }
+
+    #[test]
+    fn test_missing_task() {
+        let rake_detect = RakeDetect::from_str("rake sitemap:generate\n").unwrap();
+        let tasks = vec![
+            String::from("sitemap:generate"),
+            String::from("bootsnap:precompile"),
+        ];
+
+        assert_eq!(
+            Some("bootsnap:precompile"),
+            missing_task(&rake_detect, &tasks)
+        );
+        assert_eq!(None, missing_task(&rake_detect, &tasks[..1]));
+    }
+}
@@ -6,9 +6,12 @@ use commons::output::{
     fmt::{self, DEBUG_INFO},
 };
 
+use crate::bundler_credentials::Redact;
+use crate::project_config::{self, ProjectConfigError};
 use crate::RubyBuildpackError;
 use fun_run::{CmdError, CommandWithName};
 use indoc::formatdoc;
+use libcnb::Env;
 
 pub(crate) fn on_error(err: libcnb::Error<RubyBuildpackError>) {
     let log = BuildLog::new(std::io::stdout()).without_buildpack_name();
@@ -61,6 +64,7 @@ fn log_our_error(mut log: Box<dyn StartedLogger>, error: RubyBuildpackError) {
                         fmt::value(dir.to_string_lossy())
                     )),
                     Command::new("ls").args(["la", &dir.to_string_lossy()]),
+                    &Redact::from_env(&Env::from_current()),
                 );
             }
 
@@ -76,6 +80,65 @@ fn log_our_error(mut log: Box<dyn StartedLogger>, error: RubyBuildpackError) {
                 {git_branch_url}
             "});
         }
+        RubyBuildpackError::LockfileMissingPlatform(platform) => {
+            let add_platform = fmt::command(format!("bundle lock --add-platform {platform}"));
+            log.announce().error(&formatdoc! {"
+                Error: `Gemfile.lock` is missing the `{platform}` platform
+
+                The `PLATFORMS` section of your `Gemfile.lock` does not include the platform
+                your application is being built on. This commonly happens when the lockfile was
+                generated on macOS. Without it, bundler cannot install gems with native
+                extensions such as `nokogiri`.
+
+                To fix this run:
+
+                {add_platform}
+
+                Then commit the results to git and push again.
+
+                Alternatively, to have the buildpack run this command for you on every build set
+                the environment variable:
+
+                HEROKU_BUNDLE_LOCK_ADD_PLATFORM=1
+            "});
+        }
+        RubyBuildpackError::LockfileAddPlatformError(error) => {
+            let local_command = local_command_debug(&error);
+            log.section(DEBUG_INFO)
+                .step(&error.to_string())
+                .end_section()
+                .announce()
+                .error(&formatdoc! {"
+                    Error adding platform to `Gemfile.lock`
+
+                    The buildpack tried to add your application's build platform to the `PLATFORMS`
+                    section of your `Gemfile.lock` because `HEROKU_BUNDLE_LOCK_ADD_PLATFORM` is set,
+                    but the command failed.
+
+                    {local_command}
+
+                    Use the information above to debug further.
+                "});
+        }
+        RubyBuildpackError::LockfileAddPlatformChangedVersions(changed) => {
+            let changed = changed
+                .iter()
+                .map(|gem| format!("- {gem}"))
+                .collect::<Vec<_>>()
+                .join("\n");
+            log.announce().error(&formatdoc! {"
+                Error adding platform to `Gemfile.lock`
+
+                The buildpack tried to add your application's build platform to the `PLATFORMS`
+                section of your `Gemfile.lock` because `HEROKU_BUNDLE_LOCK_ADD_PLATFORM` is set,
+                but doing so changed the versions of these gems:
+
+                {changed}
+
+                Your `Gemfile.lock` was not modified. Run `bundle lock --add-platform` locally,
+                review the results, then commit them to git and push again.
+            "});
+        }
         RubyBuildpackError::RubyInstallError(error) => {
             // Future:
             // - In the future use a manifest file to list if version is available on a different stack
@@ -93,13 +156,17 @@ fn log_our_error(mut log: Box<dyn StartedLogger>, error: RubyBuildpackError) {
                     {ruby_versions_url}
                 "});
         }
-        RubyBuildpackError::GemInstallBundlerCommandError(error) => {
+        RubyBuildpackError::GemInstallBundlerCommandError(error, redact) => {
             log = log
                 .section(DEBUG_INFO)
                 .step(&error.to_string())
                 .end_section();
 
-            log = debug_cmd(log.section(DEBUG_INFO), Command::new("gem").arg("env"));
+            log = debug_cmd(
+                log.section(DEBUG_INFO),
+                Command::new("gem").arg("env"),
+                &redact,
+            );
 
             log.announce().error(&formatdoc! {"
                 Error installing bundler
@@ -137,6 +204,88 @@ fn log_our_error(mut log: Box<dyn StartedLogger>, error: RubyBuildpackError) {
                     Use the information above to debug further.
                 "});
         }
+        RubyBuildpackError::GemsLayerPruneError(error) => {
+            log.section(DEBUG_INFO)
+                .step(&error.to_string())
+                .end_section()
+                .announce()
+                .error(&formatdoc! {"
+                    Error pruning installed gems
+
+                    After installing your application's dependencies, the Ruby buildpack removes
+                    files that are not needed at runtime, such as downloaded `.gem` files and native
+                    extension build artifacts. An error occurred while removing or moving these files.
+
+                    This type of error usually indicates there's nothing wrong with your application.
+                    Please retry your build.
+                "});
+        }
+        RubyBuildpackError::ProcfileReadError(path, error) => {
+            log.section(DEBUG_INFO)
+                .step(&error.to_string())
+                .end_section()
+                .announce()
+                .error(&formatdoc! {"
+                    Error reading `Procfile`
+
+                    The Ruby buildpack reads process types from the `Procfile` in the root of
+                    your application. An error occurred while reading {path}.
+
+                    This type of error usually indicates there's nothing wrong with your application.
+                    Please retry your build.
+                ", path = fmt::value(path.to_string_lossy())});
+        }
+        RubyBuildpackError::ProcfileParseError(error) => {
+            log.announce().error(&formatdoc! {"
+                Error: Invalid `Procfile`
+
+                The `Procfile` in the root of your application could not be parsed:
+
+                {error}
+
+                Each line must declare a process type and the command to run it, for example:
+
+                    web: bundle exec puma -C config/puma.rb
+
+                Fix the `Procfile`, commit the change and push again.
+            "});
+        }
+        RubyBuildpackError::GemAuditError(error) => {
+            log.section(DEBUG_INFO)
+                .step(&error.to_string())
+                .end_section()
+                .announce()
+                .error(&formatdoc! {"
+                    Error reading gem advisory database
+
+                    The Ruby buildpack checks your application's gems against a local copy of
+                    the `ruby-advisory-db` because `HEROKU_GEM_AUDIT` is set. An advisory file
+                    could not be read.
+
+                    Ensure `HEROKU_GEM_AUDIT_DB_PATH` points to a valid `ruby-advisory-db`
+                    checkout, or unset `HEROKU_GEM_AUDIT` to skip this check.
+                "});
+        }
+        RubyBuildpackError::GemAuditHighSeverity(findings) => {
+            let findings = findings
+                .iter()
+                .map(|finding| format!("- {finding}"))
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            log.announce().error(&formatdoc! {"
+                Error: Gems with high severity security advisories found
+
+                The following gems in your `Gemfile.lock` have known high or critical
+                severity security advisories:
+
+                {findings}
+
+                Upgrade these gems to a patched version, commit the updated `Gemfile.lock`
+                and push again. To report these as warnings instead, unset the environment
+                variable `HEROKU_GEM_AUDIT_FAIL_ON_HIGH`.
+            "});
+        }
         RubyBuildpackError::BundleInstallDigestError(path, error) => {
             log = log
                 .section(DEBUG_INFO)
@@ -150,6 +299,7 @@ fn log_our_error(mut log: Box<dyn StartedLogger>, error: RubyBuildpackError) {
                         fmt::value(dir.to_string_lossy())
                     )),
                     Command::new("ls").args(["la", &dir.to_string_lossy()]),
+                    &Redact::from_env(&Env::from_current()),
                 );
             }
 
@@ -163,7 +313,7 @@ fn log_our_error(mut log: Box<dyn StartedLogger>, error: RubyBuildpackError) {
                 Ensure that the permissions on the files in your application directory are correct and that
                 all symlinks correctly resolve.
 
-                If you're unable to resolve this error, you can disable the the digest feature by
+                If you're unable to resolve this error, you can disable the digest feature by
                 setting the environment variable:
 
                 HEROKU_SKIP_BUNDLE_DIGEST=1
@@ -189,8 +339,225 @@ fn log_our_error(mut log: Box<dyn StartedLogger>, error: RubyBuildpackError) {
                 Use the information above to debug further.
             "});
         }
+        RubyBuildpackError::RakeDetectDigestError(path, error) => {
+            log = log
+                .section(DEBUG_INFO)
+                .step(&error.to_string())
+                .end_section();
+
+            if let Some(dir) = path.parent() {
+                log = debug_cmd(
+                    log.section(&format!(
+                        "{DEBUG_INFO} Contents of the {} directory",
+                        fmt::value(dir.to_string_lossy())
+                    )),
+                    Command::new("ls").args(["la", &dir.to_string_lossy()]),
+                    &Redact::from_env(&Env::from_current()),
+                );
+            }
+
+            log.announce().error(&formatdoc! {"
+                Error generating file digest
+
+                An error occurred while generating a file digest. To avoid running `rake -P`
+                on every build, the Ruby buildpack converts your `Gemfile.lock`, `Rakefile`
+                and `lib/tasks` into a digest to use in cache invalidation.
+
+                Ensure that the permissions on the files in your application directory are correct and that
+                all symlinks correctly resolve.
+
+                If you're unable to resolve this error, you can disable the digest feature by
+                setting the environment variable:
+
+                HEROKU_SKIP_RAKE_DIGEST=1
+            "});
+        }
+        RubyBuildpackError::ProjectConfigError(ProjectConfigError::InvalidEnv(
+            key,
+            value,
+            expected,
+        )) => {
+            let expected = project_config::expected_values(expected);
+            log.announce().error(&formatdoc! {"
+                Error: Invalid value for `{key}`
+
+                The environment variable `{key}` is set to {value:?}, which is not supported.
+                Expected one of {expected}.
+
+                Change or remove the environment variable and push again.
+            "});
+        }
+        RubyBuildpackError::ProjectConfigError(error) => {
+            log.section(DEBUG_INFO)
+                .step(&error.to_string())
+                .end_section()
+                .announce()
+                .error(&formatdoc! {"
+                    Error: Invalid `project.toml`
+
+                    The Ruby buildpack reads settings from the `[com.heroku.buildpacks.ruby]`
+                    table of the `project.toml` in the root of your application. The file could
+                    not be read or contains invalid settings.
+
+                    Supported settings are `rake_tasks` (a list of task names),
+                    `rake_tasks_replace_assets` (a boolean), `assets_precompile` (one of
+                    `auto`, `skip`, or `manifest`), and `assets_prefix` (a path such as
+                    `/assets`).
+
+                    Fix the setting and push again.
+                "});
+        }
+        RubyBuildpackError::AssetsManifestMissing(assets_dir) => {
+            let assets_dir =
+                replace_app_path_with_relative(fmt::value(assets_dir.to_string_lossy()));
+            log.announce().error(&formatdoc! {"
+                Error: Precompiled assets not found
+
+                Asset precompilation is set to `manifest`, which uses assets committed to git
+                instead of running `rake assets:precompile`. No `.sprockets-manifest-*.json`,
+                `manifest-*.json`, or `.manifest.json` manifest file was found in {assets_dir}.
+
+                Ensure your precompiled assets are committed to git, or set
+                `HEROKU_ASSETS_PRECOMPILE=auto` to compile them during the build.
+            "});
+        }
+        RubyBuildpackError::RakeTaskNotFound(task) => {
+            let task = fmt::value(task);
+            let rake_detect_cmd = fmt::command("bundle exec rake -P");
+
+            log.announce().error(&formatdoc! {"
+                Error: Rake task {task} not found
+
+                The rake task {task} is configured to run during the build through
+                `HEROKU_RAKE_TASKS` or `rake_tasks` in `project.toml`, but it is not listed
+                by {rake_detect_cmd}.
+
+                Ensure the task is listed when running {rake_detect_cmd} locally, or remove
+                it from the configured rake tasks.
+            "});
+        }
+        RubyBuildpackError::NodeNotFound(reason) => {
+            let node = fmt::value("node");
+            let pack_build =
+                fmt::command("pack build <app> --buildpack heroku/nodejs --buildpack heroku/ruby");
+
+            log.announce().error(&formatdoc! {"
+                Error: Node.js not found
+
+                Compiling assets needs Node.js for {reason}, but {node} is not on the PATH.
+                Add a Node.js buildpack such as `heroku/nodejs` before the Ruby buildpack, i.e.:
+
+                {pack_build}
+            "});
+        }
+        RubyBuildpackError::RakeTasksWithoutRake(tasks) => {
+            let tasks = tasks.iter().map(fmt::value).collect::<Vec<_>>().join(", ");
+
+            log.announce().error(&formatdoc! {"
+                Error: Rake is not available to run {tasks}
+
+                Rake tasks are configured to run during the build through `HEROKU_RAKE_TASKS`
+                or `rake_tasks` in `project.toml`, but rake cannot run. Rake tasks need the
+                `rake` gem in your `Gemfile.lock` and a `Rakefile` in the root of your
+                application.
+
+                Add them to your application, or remove the configured rake tasks.
+            "});
+        }
+        RubyBuildpackError::RakeTaskFailed(task, error) => {
+            let task = fmt::value(task);
+            let local_command = local_command_debug(&error);
+            log = log
+                .section(DEBUG_INFO)
+                .step(&error.to_string())
+                .end_section();
+
+            log.announce().error(&formatdoc! {"
+                Error running rake task {task}
+
+                An error occured while running the configured rake task {task}.
+
+                {local_command}
+
+                Use the information above to debug further.
+            "});
+        }
+        RubyBuildpackError::CommandTimedOut(timed_out) => {
+            let name = replace_app_path_with_relative(fmt::command(&timed_out.name));
+            let elapsed = fmt::duration(&timed_out.elapsed);
+            let env_var = timed_out.command.env_var();
+            let set_timeout = fmt::value(format!("{env_var}=<seconds>"));
+            let disable_timeout = fmt::value(format!("{env_var}=0"));
+            if !timed_out.tail.is_empty() {
+                log = log
+                    .section(&format!(
+                        "Last output from {name} {}",
+                        fmt::details("before it was stopped")
+                    ))
+                    .step(&timed_out.tail)
+                    .end_section();
+            }
+
+            log.announce().error(&formatdoc! {"
+                Error: Command timed out
+
+                The command {name} did not finish after running for {elapsed}
+                and was stopped along with any processes it started.
+
+                A command that runs this long is usually waiting on something that is not
+                available at build time, such as a network service or user input. Use the
+                output above to debug further.
+
+                To change the time limit set {set_timeout}, or set {disable_timeout}
+                to remove it.
+            "});
+        }
+        RubyBuildpackError::InvalidTimeout(env_var, value) => {
+            let value = fmt::value(value);
+            let env_var = fmt::value(env_var);
+            log.announce().error(&formatdoc! {"
+                Error: Invalid timeout {value} for {env_var}
+
+                Timeouts are a whole number of seconds, i.e. `1800` for 30 minutes, or `0`
+                to run the command without a time limit.
+
+                Fix the value and push again.
+            "});
+        }
+        RubyBuildpackError::BootsnapPrecompileFailed(error) => {
+            let local_command = local_command_debug(&error);
+            log = log
+                .section(DEBUG_INFO)
+                .step(&error.to_string())
+                .end_section();
+
+            log.announce().error(&formatdoc! {"
+                Error precompiling bootsnap cache
+
+                An error occured while compiling your application and gems into the bootsnap
+                cache. This usually means a Ruby or YAML file could not be parsed.
+
+                {local_command}
+
+                Use the information above to debug further.
+            "});
+        }
         RubyBuildpackError::RakeAssetsPrecompileFailed(error) => {
             let local_command = local_command_debug(&error);
+            let database_help = if crate::steps::is_database_error(&error) {
+                let dummy_database = fmt::value("HEROKU_ASSETS_DUMMY_DATABASE=1");
+                let secret_key_base_dummy = fmt::value("SECRET_KEY_BASE_DUMMY=1");
+                formatdoc! {"
+                    The output indicates your app tried to connect to a database or read production
+                    credentials while compiling assets, which are not available at build time.
+                    Rails 7.1+ apps are built with {secret_key_base_dummy}. For older apps
+                    you can set {dummy_database} to use a placeholder database URL
+                    while compiling assets.
+
+                "}
+            } else {
+                String::new()
+            };
             log = log
                 .section(DEBUG_INFO)
                 .step(&error.to_string())
@@ -201,6 +568,23 @@ fn log_our_error(mut log: Box<dyn StartedLogger>, error: RubyBuildpackError) {
 
                 An error occured while compiling assets via rake command.
 
+                {database_help}{local_command}
+
+                Use the information above to debug further.
+            "});
+        }
+        RubyBuildpackError::AssetsCompileFailed(error) => {
+            let local_command = local_command_debug(&error);
+            log = log
+                .section(DEBUG_INFO)
+                .step(&error.to_string())
+                .end_section();
+
+            log.announce().error(&formatdoc! {"
+                Error compiling assets
+
+                An error occured while compiling assets via your framework's asset command.
+
                 {local_command}
 
                 Use the information above to debug further.
@@ -225,15 +609,23 @@ fn log_our_error(mut log: Box<dyn StartedLogger>, error: RubyBuildpackError) {
                 all symlinks correctly resolve.
             "});
         }
-        RubyBuildpackError::GemListGetError(error) => {
+        RubyBuildpackError::GemListGetError(error, redact) => {
             log = log
                 .section(DEBUG_INFO)
                 .step(&error.to_string())
                 .end_section();
 
-            log = debug_cmd(log.section(DEBUG_INFO), Command::new("gem").arg("env"));
+            log = debug_cmd(
+                log.section(DEBUG_INFO),
+                Command::new("gem").arg("env"),
+                &redact,
+            );
 
-            log = debug_cmd(log.section(DEBUG_INFO), Command::new("bundle").arg("env"));
+            log = debug_cmd(
+                log.section(DEBUG_INFO),
+                Command::new("bundle").arg("env"),
+                &redact,
+            );
 
             log.announce().error(&formatdoc! {"
                 Error detecting dependencies
@@ -289,13 +681,21 @@ fn replace_app_path_with_relative(contents: impl AsRef<str>) -> String {
     app_path_re.replace_all(contents.as_ref(), "./").to_string()
 }
 
-fn debug_cmd(log: Box<dyn SectionLogger>, command: &mut Command) -> Box<dyn StartedLogger> {
+/// Runs a command to help debug an error, removing credentials with `redact`
+///
+/// Output such as `bundle env` could include credentials, build `redact` from the
+/// environment of the command that failed.
+fn debug_cmd(
+    log: Box<dyn SectionLogger>,
+    command: &mut Command,
+    redact: &Redact,
+) -> Box<dyn StartedLogger> {
     let mut stream = log.step_timed_stream(&format!(
         "Running debug command {}",
         fmt::command(command.name())
     ));
 
-    match command.stream_output(stream.io(), stream.io()) {
+    match command.stream_output(redact.writer(stream.io()), redact.writer(stream.io())) {
         Ok(_) => stream.finish_timed_stream().end_section(),
         Err(e) => stream
             .finish_timed_stream()
@@ -2,7 +2,7 @@
 
 use libcnb_test::{
     assert_contains, assert_empty, BuildConfig, BuildpackReference, ContainerConfig,
-    ContainerContext, TestRunner,
+    ContainerContext, TestContext, TestRunner,
 };
 use std::thread;
 use std::time::{Duration, Instant};
@@ -21,6 +21,7 @@ fn test_default_app() {
 
             println!("{}", context.pack_stdout); // Needed to get full failure as `rebuild` truncates stdout
             assert_contains!(context.pack_stdout, "Installing webrick");
+            assert_bundle_list(&context, include_str!("fixtures/default_ruby/bundle_list.txt"));
 
             let config = context.config.clone();
             context.rebuild(config, |rebuild_context| {
@@ -104,6 +105,7 @@ fn test_ruby_app_with_yarn_app() {
             assert_contains!(
                 context.pack_stdout,
                 r#"`BUNDLE_BIN="/layers/heroku_ruby/gems/bin" BUNDLE_CLEAN="1" BUNDLE_DEPLOYMENT="1" BUNDLE_GEMFILE="/workspace/Gemfile" BUNDLE_PATH="/layers/heroku_ruby/gems" BUNDLE_WITHOUT="development:test" bundle install`"#);
+            assert_bundle_list(&context, include_str!("fixtures/yarn-ruby-app/bundle_list.txt"));
             }
         );
 }
@@ -115,6 +117,10 @@ fn test_barnes_app() {
         BuildConfig::new("heroku/builder:22", "tests/fixtures/barnes_app"),
         |context| {
             assert_contains!(context.pack_stdout, "# Heroku Ruby Buildpack");
+            assert_bundle_list(
+                &context,
+                include_str!("fixtures/barnes_app/bundle_list.txt"),
+            );
 
             context.start_container(
                 ContainerConfig::new()
@@ -153,6 +159,13 @@ fn test_barnes_app() {
     );
 }
 
+/// Gems are read from the `Gemfile.lock` and match the real `bundle list` output in
+/// the fixture, which the unit tests compare against the lockfile reader
+fn assert_bundle_list(context: &TestContext, bundle_list: &str) {
+    assert_contains!(context.pack_stdout, "Reading gems from `Gemfile.lock`");
+    assert_eq!(bundle_list, context.run_shell_command("bundle list").stdout);
+}
+
 fn request_container(
     container: &ContainerContext,
     port: u16,
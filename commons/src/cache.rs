@@ -11,5 +11,5 @@ pub use self::app_cache::{AppCache, CacheState};
 pub use self::app_cache_collection::AppCacheCollection;
 pub use self::clean::FilesWithSize;
 pub use self::config::CacheConfig;
-pub use self::config::{mib, KeepPath};
+pub use self::config::{mib, KeepPath, OverLimit};
 pub use self::error::CacheError;
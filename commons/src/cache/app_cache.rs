@@ -1,10 +1,11 @@
-use crate::cache::clean::{lru_clean, FilesWithSize};
+use crate::cache::clean::{clear_above_limit, lru_clean, FilesWithSize};
 use crate::cache::in_app_dir_cache_layer::InAppDirCacheLayer;
-use crate::cache::{CacheConfig, CacheError, KeepPath};
+use crate::cache::{CacheConfig, CacheError, KeepPath, OverLimit};
 use byte_unit::{AdjustedByte, Byte};
 use fs_extra::dir::CopyOptions;
 use libcnb::build::BuildContext;
 use libcnb::data::layer::LayerName;
+use sha2::Digest;
 use std::path::Path;
 use std::path::PathBuf;
 
@@ -30,7 +31,7 @@ use tempfile as _;
 ///
 ///# pub(crate) struct HelloWorldBuildpack;
 ///
-///  use commons::cache::{AppCache, CacheConfig, KeepPath, mib};
+///  use commons::cache::{AppCache, CacheConfig, KeepPath, OverLimit, mib};
 ///
 ///# impl Buildpack for HelloWorldBuildpack {
 ///#     type Platform = GenericPlatform;
@@ -45,7 +46,9 @@ use tempfile as _;
 ///         let config = CacheConfig {
 ///             path: context.app_dir.join("public").join("assets"),
 ///             limit: mib(100),
-///             keep_path: KeepPath::Runtime
+///             over_limit: OverLimit::RemoveOldest,
+///             keep_path: KeepPath::Runtime,
+///             invalidate_on: Vec::new(),
 ///         };
 ///
 ///         let store = AppCache::new_and_load(&context, config).unwrap();
@@ -64,9 +67,12 @@ pub struct AppCache {
     path: PathBuf,
 
     /// Prevent cache size from growing unbounded. Files over the limit
-    /// will be removed in order of least recently modified
+    /// will be removed according to `over_limit`
     limit: Byte,
 
+    /// Specify how files are removed when the cache is over the `limit`
+    over_limit: OverLimit,
+
     /// Directory where files will be cached
     cache: PathBuf,
 
@@ -175,7 +181,8 @@ impl AppCache {
     ///
     /// Afterwards, files in the cache will be cleaned:
     /// If the cache directory is above the given `limit` then
-    /// files will be deleted in LRU order based on disk mtime.
+    /// files will be deleted in LRU order based on disk mtime,
+    /// or all of them with `OverLimit::RemoveAll`.
     ///
     /// If any files were removed in this process then they will
     /// be returned via `Some`. If no files were removed, `None`
@@ -190,7 +197,10 @@ impl AppCache {
     /// - If metadata of a file cannot be read, an error will be raised
     pub fn save_and_clean(&self) -> Result<Option<FilesWithSize>, CacheError> {
         self.save()?;
-        lru_clean(&self.cache, self.limit)
+        match self.over_limit {
+            OverLimit::RemoveOldest => lru_clean(&self.cache, self.limit),
+            OverLimit::RemoveAll => clear_above_limit(&self.cache, self.limit),
+        }
     }
 
     /// Returns an enum representing the state
@@ -217,6 +227,9 @@ pub enum CacheState {
 
     /// Cache was previously created, it is non-empty
     ExistsWithContents,
+
+    /// Cache was cleared because a file in `invalidate_on` changed
+    Invalidated,
 }
 
 /// Current state of the path
@@ -243,21 +256,30 @@ pub fn build<B: libcnb::Buildpack>(
     let CacheConfig {
         path,
         limit,
+        over_limit,
         keep_path,
+        invalidate_on,
     } = config;
 
+    let key = files_digest(&invalidate_on)?;
     let layer_name = create_layer_name(&context.app_dir, &path)?;
-    let create_state = layer_name_cache_state(&context.layers_dir, &layer_name);
+    let mut create_state = layer_name_cache_state(&context.layers_dir, &layer_name);
 
     let layer = context
-        .handle_layer(layer_name, InAppDirCacheLayer::new(path.clone()))
+        .handle_layer(layer_name, InAppDirCacheLayer::new(path.clone(), key))
         .map_err(|error| CacheError::InternalLayerError(format!("{error:?}")))?;
 
     let cache = layer.path;
 
+    // A kept layer still has its contents, an empty one was recreated
+    if create_state == CacheState::ExistsWithContents && is_empty_dir(&cache) {
+        create_state = CacheState::Invalidated;
+    }
+
     Ok(AppCache {
         path,
         limit,
+        over_limit,
         cache,
         keep_path,
         cache_state: create_state,
@@ -324,6 +346,29 @@ fn remove_path_save(store: &AppCache) -> Result<&AppCache, CacheError> {
     Ok(store)
 }
 
+/// Digest of the contents of the given files, `None` when there are no files
+///
+/// # Errors
+///
+/// - If a file exists but cannot be read
+fn files_digest(files: &[PathBuf]) -> Result<Option<String>, CacheError> {
+    if files.is_empty() {
+        return Ok(None);
+    }
+
+    let mut hasher = sha2::Sha256::new();
+    for file in files {
+        hasher.update(file.to_string_lossy().as_bytes());
+        match fs_err::read(file) {
+            Ok(contents) => hasher.update(contents),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {}
+            Err(error) => return Err(CacheError::IoError(error)),
+        }
+    }
+
+    Ok(Some(format!("{:x}", hasher.finalize())))
+}
+
 /// Converts a path inside of an app to a valid layer name for libcnb.
 fn create_layer_name(app_root: &Path, path: &Path) -> Result<LayerName, CacheError> {
     let name = path
@@ -386,6 +431,23 @@ mod tests {
         assert_eq!(layer_name!("cache_my_input"), layer);
     }
 
+    #[test]
+    fn test_files_digest() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let files = [tmpdir.path().join("yarn.lock")];
+
+        assert_eq!(None, files_digest(&[]).unwrap());
+
+        let missing = files_digest(&files).unwrap();
+        fs_err::write(&files[0], "left-pad@1.3.0").unwrap();
+        let written = files_digest(&files).unwrap();
+        assert_ne!(missing, written);
+        assert_eq!(written, files_digest(&files).unwrap());
+
+        fs_err::write(&files[0], "left-pad@1.3.1").unwrap();
+        assert_ne!(written, files_digest(&files).unwrap());
+    }
+
     #[test]
     fn test_copying_back_to_cache() {
         let tmpdir = tempfile::tempdir().unwrap();
@@ -395,6 +457,7 @@ mod tests {
             path: app_path.clone(),
             cache: cache_path,
             limit: Byte::from_bytes(512),
+            over_limit: OverLimit::RemoveOldest,
             keep_path: KeepPath::Runtime,
             cache_state: CacheState::NewEmpty,
         };
@@ -426,6 +489,7 @@ mod tests {
             path: app_path.clone(),
             cache: cache_path,
             limit: Byte::from_bytes(512),
+            over_limit: OverLimit::RemoveOldest,
             keep_path: KeepPath::BuildOnly,
             cache_state: CacheState::NewEmpty,
         };
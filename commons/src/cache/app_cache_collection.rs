@@ -41,6 +41,9 @@ impl<'a> AppCacheCollection<'a> {
                         CacheState::NewEmpty => format!("Creating cache for {path}"),
                         CacheState::ExistsEmpty => format!("Loading (empty) cache for {path}"),
                         CacheState::ExistsWithContents => format!("Loading cache for {path}"),
+                        CacheState::Invalidated => {
+                            format!("Clearing cache for {path} (invalidated by changed files)")
+                        }
                     });
                     store
                 })
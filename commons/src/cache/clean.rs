@@ -33,6 +33,40 @@ pub(crate) fn lru_clean(path: &Path, limit: Byte) -> Result<Option<FilesWithSize
     }
 }
 
+/// Remove every file in the cache when it is above a byte limit
+///
+/// For directories that cannot be used partially, such as `node_modules`,
+/// removing the oldest files would leave a broken copy in the cache.
+///
+/// # Errors
+///
+/// - Metadata from a file in the ``cache_path`` cannot be retrieved from the OS (`IoError`).
+/// - If there's an OS error while deleting a file or directory.
+pub(crate) fn clear_above_limit(
+    path: &Path,
+    limit: Byte,
+) -> Result<Option<FilesWithSize>, CacheError> {
+    let files = files(path)?;
+    let bytes = files.iter().map(|p| u128::from(p.size)).sum::<u128>();
+    if bytes <= limit.get_bytes() {
+        return Ok(None);
+    }
+
+    for entry in fs_err::read_dir(path).map_err(CacheError::IoError)? {
+        let entry = entry.map_err(CacheError::IoError)?;
+        if entry.path().is_dir() {
+            fs_err::remove_dir_all(entry.path()).map_err(CacheError::IoError)?;
+        } else {
+            fs_err::remove_file(entry.path()).map_err(CacheError::IoError)?;
+        }
+    }
+
+    Ok(Some(FilesWithSize {
+        bytes,
+        files: files.into_iter().map(|file| file.path).collect(),
+    }))
+}
+
 /// Converts all files in a directory (recursively) into a `MiniPathModSize`
 /// so they can be sorted by modified date and total size calculated.
 fn files(cache_path: &Path) -> Result<Vec<MiniPathModSize>, CacheError> {
@@ -198,6 +232,28 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_clear_above_limit_removes_everything() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let dir = tmpdir.path().join("node_modules");
+        fs_err::create_dir_all(dir.join("left-pad")).unwrap();
+        fs_err::write(dir.join("left-pad").join("index.js"), "module.exports = 1").unwrap();
+        fs_err::write(dir.join(".package-lock.json"), "{}").unwrap();
+
+        assert_eq!(
+            None,
+            clear_above_limit(&dir, Byte::from_bytes(n_mib_bytes!(1))).unwrap()
+        );
+        assert!(dir.join("left-pad").join("index.js").exists());
+
+        let removed = clear_above_limit(&dir, Byte::from_bytes(10))
+            .unwrap()
+            .unwrap();
+        assert_eq!(removed.files.len(), 2);
+        assert!(dir.exists());
+        assert_eq!(0, fs_err::read_dir(&dir).unwrap().count());
+    }
+
     #[test]
     fn test_lru_does_not_grab_directories() {
         let tmpdir = tempfile::tempdir().unwrap();
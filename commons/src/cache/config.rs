@@ -8,12 +8,19 @@ pub struct CacheConfig {
     /// Path to the directory you want to cache
     pub path: PathBuf,
 
-    /// Prevent cache size from growing unbounded. Files over the limit
-    /// will be removed in order of least recently modified
+    /// Prevent cache size from growing unbounded, see `over_limit`
     pub limit: Byte,
 
+    /// Specify how files are removed when the cache is over the `limit`
+    pub over_limit: OverLimit,
+
     /// Specify what happens to the application path while it's being stored
     pub keep_path: KeepPath,
+
+    /// Clear the cache when the contents of any of these files change between builds,
+    /// for example a lockfile for the dependencies being cached. Missing files are
+    /// treated as empty. When empty the cache is never cleared this way.
+    pub invalidate_on: Vec<PathBuf>,
 }
 
 /// Indicates whether we want the cache to be available at runtime or not.
@@ -26,6 +33,17 @@ pub enum KeepPath {
     BuildOnly,
 }
 
+/// How a cache over its limit is cleaned
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OverLimit {
+    /// Remove files in order of least recently modified until under the limit
+    RemoveOldest,
+
+    /// Remove every file, for directories that only work when complete such as
+    /// `node_modules`
+    RemoveAll,
+}
+
 /// Returns a `Byte` value containing the number
 /// of mebibytes given.
 #[must_use]
@@ -24,18 +24,25 @@ use std::path::PathBuf;
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub(crate) struct InAppDirCacheLayer<B> {
     pub app_dir_path: PathBuf,
+    pub key: Option<String>,
     buildpack: PhantomData<B>,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub(crate) struct InAppDirCacheLayerMetadata {
     app_dir_path: PathBuf,
+
+    /// Digest of the files the cache is invalidated on, layers from before
+    /// this was added deserialize as `None`
+    #[serde(default)]
+    key: Option<String>,
 }
 
 impl<B> InAppDirCacheLayer<B> {
-    pub fn new(app_dir_path: PathBuf) -> Self {
+    pub fn new(app_dir_path: PathBuf, key: Option<String>) -> Self {
         Self {
             app_dir_path,
+            key,
             buildpack: PhantomData,
         }
     }
@@ -63,6 +70,7 @@ where
     ) -> Result<LayerResult<Self::Metadata>, B::Error> {
         LayerResultBuilder::new(InAppDirCacheLayerMetadata {
             app_dir_path: self.app_dir_path.clone(),
+            key: self.key.clone(),
         })
         .build()
     }
@@ -72,7 +80,8 @@ where
         _context: &BuildContext<Self::Buildpack>,
         layer_data: &LayerData<Self::Metadata>,
     ) -> Result<ExistingLayerStrategy, B::Error> {
-        if self.app_dir_path == layer_data.content_metadata.metadata.app_dir_path {
+        let metadata = &layer_data.content_metadata.metadata;
+        if self.app_dir_path == metadata.app_dir_path && self.key == metadata.key {
             Ok(ExistingLayerStrategy::Keep)
         } else {
             Ok(ExistingLayerStrategy::Recreate)
@@ -0,0 +1,225 @@
+use crate::gem_version::{GemVersion, VersionError};
+use std::fmt;
+use std::str::FromStr;
+
+/// # Match gem versions against a requirement such as `~> 2.2, >= 2.2.3.1`
+///
+/// Based off of Ruby's `Gem::Requirement` logic:
+///
+/// - <https://github.com/rubygems/rubygems/blob/ecc8e895b69063562b9bf749b353948e051e4171/lib/rubygems/requirement.rb>
+///
+/// Every comma separated constraint must be satisfied. A constraint without an
+/// operator is treated as `=`.
+///
+/// Example:
+///
+/// ```rust
+/// use std::str::FromStr;
+/// use commons::gem_requirement::GemRequirement;
+/// use commons::gem_version::GemVersion;
+///
+/// let requirement = GemRequirement::from_str("~> 2.2, >= 2.2.3.1").unwrap();
+/// assert!(requirement.satisfied_by(&GemVersion::from_str("2.2.8").unwrap()));
+/// assert!(!requirement.satisfied_by(&GemVersion::from_str("2.2.3").unwrap()));
+/// assert!(!requirement.satisfied_by(&GemVersion::from_str("3.0.0").unwrap()));
+/// ```
+#[derive(Debug)]
+pub struct GemRequirement {
+    constraints: Vec<Constraint>,
+    source: String,
+}
+
+#[derive(Debug)]
+struct Constraint {
+    operator: Operator,
+    version: GemVersion,
+
+    /// Upper bound for `~>`, i.e. `< 2.3` for `~> 2.2.1`
+    pessimistic_limit: Option<GemVersion>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Equal,
+    NotEqual,
+    Greater,
+    Less,
+    GreaterOrEqual,
+    LessOrEqual,
+    Pessimistic,
+}
+
+impl GemRequirement {
+    #[must_use]
+    pub fn satisfied_by(&self, version: &GemVersion) -> bool {
+        self.constraints.iter().all(|constraint| {
+            let target = &constraint.version;
+            match constraint.operator {
+                Operator::Equal => version == target,
+                Operator::NotEqual => version != target,
+                Operator::Greater => version > target,
+                Operator::Less => version < target,
+                Operator::GreaterOrEqual => version >= target,
+                Operator::LessOrEqual => version <= target,
+                Operator::Pessimistic => match &constraint.pessimistic_limit {
+                    Some(limit) => version >= target && version < limit,
+                    None => version >= target,
+                },
+            }
+        })
+    }
+}
+
+impl fmt::Display for GemRequirement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl FromStr for GemRequirement {
+    type Err = VersionError;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        let constraints = string
+            .split(',')
+            .map(str::trim)
+            .filter(|constraint| !constraint.is_empty())
+            .map(constraint)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(GemRequirement {
+            constraints,
+            source: string.trim().to_string(),
+        })
+    }
+}
+
+fn constraint(string: &str) -> Result<Constraint, VersionError> {
+    // Longest operators first so `>=` is not read as `>`
+    let operators = [
+        ("~>", Operator::Pessimistic),
+        (">=", Operator::GreaterOrEqual),
+        ("<=", Operator::LessOrEqual),
+        ("!=", Operator::NotEqual),
+        ("=", Operator::Equal),
+        (">", Operator::Greater),
+        ("<", Operator::Less),
+    ];
+    let (operator, version) = operators
+        .iter()
+        .find_map(|(prefix, operator)| {
+            string
+                .strip_prefix(prefix)
+                .map(|version| (*operator, version.trim()))
+        })
+        .unwrap_or((Operator::Equal, string));
+
+    let pessimistic_limit = if operator == Operator::Pessimistic {
+        Some(bump(version)?)
+    } else {
+        None
+    };
+
+    Ok(Constraint {
+        operator,
+        version: GemVersion::from_str(version)?,
+        pessimistic_limit,
+    })
+}
+
+/// Returns the next significant release i.e. `2.3` for `2.2.1` and `3` for `2.2`
+///
+/// Uses the version as written, `GemVersion` drops trailing zeros which changes the result.
+fn bump(version: &str) -> Result<GemVersion, VersionError> {
+    let mut segments = version
+        .trim()
+        .split('.')
+        .take_while(|segment| segment.chars().all(|c| c.is_ascii_digit()))
+        .map(|segment| {
+            segment
+                .parse::<u64>()
+                .map_err(|_| VersionError::InvalidVersion(version.to_string()))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if segments.len() > 1 {
+        segments.pop();
+    }
+    let last = segments
+        .last_mut()
+        .ok_or_else(|| VersionError::InvalidVersion(version.to_string()))?;
+    *last += 1;
+
+    GemVersion::from_str(
+        &segments
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("."),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    // https://github.com/rubygems/rubygems/blob/ecc8e895b69063562b9bf749b353948e051e4171/test/rubygems/test_gem_requirement.rb
+    fn test_satisfied_by() {
+        assert!(r("= 1.0").satisfied_by(&v("1.0.0")));
+        assert!(r("1.0").satisfied_by(&v("1")));
+        assert!(!r("= 1.0").satisfied_by(&v("1.0.1")));
+
+        assert!(r("!= 1.2").satisfied_by(&v("1.1")));
+        assert!(!r("!= 1.2").satisfied_by(&v("1.2")));
+
+        assert!(r("> 1.0").satisfied_by(&v("1.0.1")));
+        assert!(!r("> 1.0").satisfied_by(&v("1.0")));
+        assert!(r("< 1.0").satisfied_by(&v("0.9")));
+        assert!(r(">= 1.0").satisfied_by(&v("1.0")));
+        assert!(r("<= 1.0").satisfied_by(&v("1.0")));
+        assert!(r("<= 1.0").satisfied_by(&v("1.0.a")));
+
+        assert!(r(">= 1.2, < 2").satisfied_by(&v("1.9.9")));
+        assert!(!r(">= 1.2, < 2").satisfied_by(&v("2.0")));
+    }
+
+    #[test]
+    fn test_pessimistic() {
+        assert!(r("~> 1.4").satisfied_by(&v("1.4")));
+        assert!(r("~> 1.4").satisfied_by(&v("1.9.3")));
+        assert!(!r("~> 1.4").satisfied_by(&v("2.0")));
+        assert!(!r("~> 1.4").satisfied_by(&v("1.3.9")));
+
+        assert!(r("~> 1.4.4").satisfied_by(&v("1.4.9")));
+        assert!(!r("~> 1.4.4").satisfied_by(&v("1.5")));
+
+        // Trailing zeros are significant for the upper bound
+        assert!(r("~> 2.2.0").satisfied_by(&v("2.2.9")));
+        assert!(!r("~> 2.2.0").satisfied_by(&v("2.3")));
+
+        assert!(r("~> 5.2.4, >= 5.2.4.3").satisfied_by(&v("5.2.4.3")));
+        assert!(!r("~> 5.2.4, >= 5.2.4.3").satisfied_by(&v("5.2.4.2")));
+        assert!(r("~> 5.2.4, >= 5.2.4.3").satisfied_by(&v("5.2.5")));
+        assert!(!r("~> 5.2.4, >= 5.2.4.3").satisfied_by(&v("5.3")));
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(GemRequirement::from_str("~> junk").is_err());
+        assert!(GemRequirement::from_str(">= 1..2").is_err());
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!("~> 2.2, >= 2.2.3.1", r(" ~> 2.2, >= 2.2.3.1").to_string());
+    }
+
+    fn r(s: &str) -> GemRequirement {
+        s.parse().unwrap()
+    }
+
+    fn v(s: &str) -> GemVersion {
+        s.parse().unwrap()
+    }
+}
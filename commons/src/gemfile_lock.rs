@@ -2,6 +2,7 @@ use core::str::FromStr;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::fmt::Display;
+use std::path::PathBuf;
 
 /// # Parse and store contents of Gemfile.lock
 ///
@@ -44,6 +45,20 @@ use std::fmt::Display;
 pub struct GemfileLock {
     pub bundler_version: BundlerVersion,
     pub ruby_version: RubyVersion,
+
+    /// Local gem sources from `PATH` sections, i.e. `gem "foo", path: "engines/foo"`
+    /// or a `gemspec` directive in the `Gemfile`
+    pub path_sources: Vec<PathSource>,
+
+    /// Platforms the lockfile was resolved for from the `PLATFORMS` section,
+    /// i.e. `ruby`, `x86_64-linux`, or `arm64-darwin-22`
+    pub platforms: Vec<String>,
+
+    /// Every resolved gem from the `GEM`, `GIT`, and `PATH` sections
+    pub specs: Vec<LockedSpec>,
+
+    /// Names of the gems listed in the `Gemfile` from the `DEPENDENCIES` section
+    pub dependencies: Vec<String>,
 }
 
 impl GemfileLock {
@@ -80,9 +95,115 @@ impl GemfileLock {
     }
 }
 
+/// A directory of gems recorded in a `PATH` section of the `Gemfile.lock`
+///
+/// ```text
+/// PATH
+///   remote: engines/foo
+///   specs:
+///     foo (0.1.0)
+///       rails (>= 7.0)
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PathSource {
+    /// Location of the gem directory, relative to the `Gemfile` unless absolute
+    pub remote: PathBuf,
+
+    /// Names of the gems provided by the directory
+    pub gems: Vec<String>,
+}
+
+/// A resolved gem from the `specs:` of a `GEM`, `GIT`, or `PATH` section
+///
+/// ```text
+/// GIT
+///   remote: https://github.com/rails/rails.git
+///   revision: abc1234def5678
+///   specs:
+///     rails (7.1.2)
+///       railties (= 7.1.2)
+/// ```
+///
+/// The same gem can be listed once per platform i.e. `nokogiri (1.15.4)` and
+/// `nokogiri (1.15.4-x86_64-linux)`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct LockedSpec {
+    pub name: String,
+    pub version: String,
+
+    /// Platform of a precompiled gem, `None` for the generic `ruby` platform
+    pub platform: Option<String>,
+
+    /// Commit of the `GIT` section the gem came from
+    pub revision: Option<String>,
+
+    /// Names of the gems this gem depends on
+    pub dependencies: Vec<String>,
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub struct ResolvedRubyVersion(pub String);
 
+impl ResolvedRubyVersion {
+    /// The version Ruby uses for binary compatibility of native extensions
+    ///
+    /// Gems are installed to a directory named after this version i.e. `ruby/3.2.0`,
+    /// it stays the same across patch releases (`3.2.2` to `3.2.3`). For `JRuby` the
+    /// Ruby language version is used. When a version cannot be parsed the full version
+    /// is returned so callers treat any change as incompatible.
+    ///
+    /// ```rust
+    /// use commons::gemfile_lock::ResolvedRubyVersion;
+    ///
+    /// let version = ResolvedRubyVersion(String::from("3.2.3"));
+    /// assert_eq!("3.2.0", &version.abi_version());
+    ///
+    /// let version = ResolvedRubyVersion(String::from("2.6.8-jruby-9.3.6.0"));
+    /// assert_eq!("2.6.0", &version.abi_version());
+    /// ```
+    #[must_use]
+    pub fn abi_version(&self) -> String {
+        let mut parts = self.0.split(['.', '-']);
+        match (parts.next(), parts.next()) {
+            (Some(major), Some(minor))
+                if [major, minor]
+                    .iter()
+                    .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit())) =>
+            {
+                format!("{major}.{minor}.0")
+            }
+            _ => self.0.clone(),
+        }
+    }
+
+    /// Name of the Ruby implementation, gems are installed to a directory with this name
+    ///
+    /// ```rust
+    /// use commons::gemfile_lock::ResolvedRubyVersion;
+    ///
+    /// assert_eq!("ruby", ResolvedRubyVersion(String::from("3.2.3")).engine());
+    /// assert_eq!("jruby", ResolvedRubyVersion(String::from("2.6.8-jruby-9.3.6.0")).engine());
+    /// ```
+    #[must_use]
+    pub fn engine(&self) -> &'static str {
+        if self.0.contains("jruby") {
+            "jruby"
+        } else {
+            "ruby"
+        }
+    }
+
+    /// The engine and ABI version i.e. `ruby-3.2.0`, native extensions are only
+    /// compatible when this is unchanged
+    ///
+    /// MRI `2.6.8` and `JRuby` `2.6.8-jruby-9.3.6.0` have the same ABI version but
+    /// cannot share native extensions.
+    #[must_use]
+    pub fn abi_key(&self) -> String {
+        format!("{}-{}", self.engine(), self.abi_version())
+    }
+}
+
 impl Display for ResolvedRubyVersion {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_str(&self.0)
@@ -139,13 +260,130 @@ impl FromStr for GemfileLock {
             None => RubyVersion::Default,
         };
 
+        let sections = sections(string);
+        let path_sources = sections
+            .iter()
+            .filter(|section| section.name == "PATH")
+            .filter_map(path_source)
+            .collect();
+
+        let platforms = sections
+            .iter()
+            .filter(|section| section.name == "PLATFORMS")
+            .flat_map(|section| section.lines.iter().map(|line| line.trim().to_string()))
+            .collect();
+
+        let specs = sections
+            .iter()
+            .filter(|section| ["GEM", "GIT", "PATH"].contains(&section.name))
+            .flat_map(locked_specs)
+            .collect();
+
+        // Path and git gems are marked with a `!` i.e. `billing!`
+        let dependencies = sections
+            .iter()
+            .filter(|section| section.name == "DEPENDENCIES")
+            .flat_map(|section| section.lines.iter())
+            .filter_map(|line| line.split_whitespace().next())
+            .map(|name| name.trim_end_matches('!').to_string())
+            .collect();
+
         Ok(Self {
             bundler_version,
             ruby_version,
+            path_sources,
+            platforms,
+            specs,
+            dependencies,
         })
     }
 }
 
+/// A top level section of a `Gemfile.lock` such as `GEM` or `PATH`
+/// along with the indented lines that belong to it.
+struct Section<'a> {
+    name: &'a str,
+    lines: Vec<&'a str>,
+}
+
+fn sections(string: &str) -> Vec<Section<'_>> {
+    let mut sections: Vec<Section<'_>> = Vec::new();
+    for line in string.lines().filter(|line| !line.trim().is_empty()) {
+        if line.starts_with(' ') {
+            if let Some(section) = sections.last_mut() {
+                section.lines.push(line);
+            }
+        } else {
+            sections.push(Section {
+                name: line.trim(),
+                lines: Vec::new(),
+            });
+        }
+    }
+    sections
+}
+
+fn path_source(section: &Section<'_>) -> Option<PathSource> {
+    let remote = section
+        .lines
+        .iter()
+        .find_map(|line| line.trim().strip_prefix("remote: "))?;
+
+    // Specs are indented four spaces, their dependencies six
+    let gems = section
+        .lines
+        .iter()
+        .filter_map(|line| line.strip_prefix("    "))
+        .filter(|line| !line.starts_with(' '))
+        .filter_map(|line| line.split_whitespace().next())
+        .map(String::from)
+        .collect();
+
+    Some(PathSource {
+        remote: PathBuf::from(remote.trim()),
+        gems,
+    })
+}
+
+fn locked_specs(section: &Section<'_>) -> Vec<LockedSpec> {
+    let revision = section
+        .lines
+        .iter()
+        .find_map(|line| line.trim().strip_prefix("revision: "))
+        .map(|revision| revision.trim().to_string());
+
+    let mut specs: Vec<LockedSpec> = Vec::new();
+    for line in &section.lines {
+        if let Some(dependency) = line.strip_prefix("      ") {
+            if let (Some(spec), Some(name)) =
+                (specs.last_mut(), dependency.split_whitespace().next())
+            {
+                spec.dependencies.push(name.to_string());
+            }
+        } else if let Some(spec) = line.strip_prefix("    ") {
+            let Some((name, version)) = spec.trim().split_once(' ') else {
+                continue;
+            };
+            let version = version.trim_start_matches('(').trim_end_matches(')');
+
+            // Rubygems normalizes `-` in versions to `.pre.` so the first `-` starts the platform
+            let (version, platform) = match version.split_once('-') {
+                Some((version, platform)) => (version, Some(platform.to_string())),
+                None => (version, None),
+            };
+
+            specs.push(LockedSpec {
+                name: name.to_string(),
+                version: version.to_string(),
+                platform,
+                revision: revision.clone(),
+                dependencies: Vec::new(),
+            });
+        }
+    }
+    specs
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -184,6 +422,82 @@ BUNDLED WITH
             info.ruby_version,
             RubyVersion::Explicit("3.1.0".to_string())
         );
+        assert_eq!(
+            info.platforms,
+            vec![
+                String::from("ruby"),
+                String::from("x86_64-darwin-20"),
+                String::from("x86_64-linux")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_path_sources() {
+        let info = GemfileLock::from_str(
+            r"
+PATH
+  remote: .
+  specs:
+    my_app (0.1.0)
+      rack
+
+PATH
+  remote: engines/billing
+  glob: *.gemspec
+  specs:
+    billing (1.2.0)
+      rails (>= 7.0)
+    billing-admin (1.2.0)
+
+GEM
+  remote: https://rubygems.org/
+  specs:
+    rack (3.0.2)
+
+PLATFORMS
+  x86_64-linux
+
+DEPENDENCIES
+  billing!
+  my_app!
+",
+        )
+        .unwrap();
+
+        assert_eq!(
+            info.path_sources,
+            vec![
+                PathSource {
+                    remote: PathBuf::from("."),
+                    gems: vec![String::from("my_app")]
+                },
+                PathSource {
+                    remote: PathBuf::from("engines/billing"),
+                    gems: vec![String::from("billing"), String::from("billing-admin")]
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ruby_abi_version() {
+        let abi = |version: &str| ResolvedRubyVersion(String::from(version)).abi_version();
+
+        assert_eq!("3.2.0", abi("3.2.2"));
+        assert_eq!("3.2.0", abi("3.2.0"));
+        assert_eq!("3.3.0", abi("3.3.0-preview1"));
+        assert_eq!("2.6.0", abi("2.6.8-jruby-9.3.6.0"));
+        assert_eq!("head", abi("head"));
+    }
+
+    #[test]
+    fn test_ruby_abi_key() {
+        let key = |version: &str| ResolvedRubyVersion(String::from(version)).abi_key();
+
+        assert_eq!("ruby-3.2.0", key("3.2.2"));
+        assert_eq!("jruby-2.6.0", key("2.6.8-jruby-9.3.6.0"));
+        assert_ne!(key("2.6.8"), key("2.6.8-jruby-9.3.6.0"));
     }
 
     #[test]
@@ -191,6 +505,83 @@ BUNDLED WITH
         let info = GemfileLock::from_str("").unwrap();
         assert_eq!(info.bundler_version, BundlerVersion::Default);
         assert_eq!(info.ruby_version, RubyVersion::Default);
+        assert!(info.path_sources.is_empty());
+        assert!(info.platforms.is_empty());
+        assert!(info.specs.is_empty());
+        assert!(info.dependencies.is_empty());
+    }
+
+    #[test]
+    fn test_specs_and_dependencies() {
+        let info = GemfileLock::from_str(
+            r"
+GIT
+  remote: https://github.com/rails/rails.git
+  revision: abc1234def5678
+  branch: main
+  specs:
+    rails (7.1.2)
+      railties (= 7.1.2)
+    railties (7.1.2)
+
+PATH
+  remote: engines/billing
+  specs:
+    billing (1.2.0)
+      rails (>= 7.0)
+
+GEM
+  remote: https://rubygems.org/
+  specs:
+    nokogiri (1.15.4)
+      racc (~> 1.4)
+    nokogiri (1.15.4-x86_64-linux)
+      racc (~> 1.4)
+    racc (1.7.3)
+
+PLATFORMS
+  ruby
+  x86_64-linux
+
+DEPENDENCIES
+  billing!
+  nokogiri (~> 1.15)
+  rails!
+",
+        )
+        .unwrap();
+
+        assert_eq!(
+            info.dependencies,
+            vec![
+                String::from("billing"),
+                String::from("nokogiri"),
+                String::from("rails")
+            ]
+        );
+        assert_eq!(info.specs.len(), 6);
+        assert_eq!(
+            info.specs[0],
+            LockedSpec {
+                name: String::from("rails"),
+                version: String::from("7.1.2"),
+                platform: None,
+                revision: Some(String::from("abc1234def5678")),
+                dependencies: vec![String::from("railties")],
+            }
+        );
+        assert_eq!(info.specs[2].name, "billing");
+        assert_eq!(info.specs[2].revision, None);
+        assert_eq!(
+            info.specs[4],
+            LockedSpec {
+                name: String::from("nokogiri"),
+                version: String::from("1.15.4"),
+                platform: Some(String::from("x86_64-linux")),
+                revision: None,
+                dependencies: vec![String::from("racc")],
+            }
+        );
     }
 
     #[test]
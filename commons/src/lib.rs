@@ -10,6 +10,7 @@ use fun_run as _;
 
 pub mod cache;
 pub mod display;
+pub mod gem_requirement;
 pub mod gem_version;
 pub mod gemfile_lock;
 pub mod layer;
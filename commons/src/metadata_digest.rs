@@ -2,6 +2,7 @@ use libcnb::{Env, Platform};
 use serde::{Deserialize, Serialize};
 use sha2::Digest;
 use std::collections::HashMap;
+use std::ffi::OsStr;
 use std::fmt::Display;
 use std::path::{Path, PathBuf};
 
@@ -158,11 +159,31 @@ pub struct MetadataDigest {
 impl MetadataDigest {
     /// Create new from inputs
     ///
+    /// Paths may be files or directories. A directory is digested from the
+    /// names and contents of every file it contains.
+    ///
     /// # Errors
     ///
     /// Errors if one of the files cannot be read from disk.
     pub fn new_env_files(platform: &impl Platform, files: &[&Path]) -> Result<Self, DigestError> {
-        let env = PlatformEnvDigest::new(platform);
+        Self::new_env_files_ignoring(platform, files, |_| false)
+    }
+
+    /// Create new from inputs, leaving out platform environment variables
+    /// where `ignore_env` returns true
+    ///
+    /// Use this for values that do not affect the outcome of the cached operation
+    /// such as credentials, so rotating them does not invalidate the cache.
+    ///
+    /// # Errors
+    ///
+    /// Errors if one of the files cannot be read from disk.
+    pub fn new_env_files_ignoring(
+        platform: &impl Platform,
+        files: &[&Path],
+        ignore_env: impl Fn(&OsStr) -> bool,
+    ) -> Result<Self, DigestError> {
+        let env = PlatformEnvDigest::new(platform, ignore_env);
         let files = PathsDigest::new(files)?;
 
         Ok(MetadataDigest {
@@ -242,10 +263,15 @@ struct ShaString(String);
 #[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq)]
 struct PlatformEnvDigest(ShaString);
 impl PlatformEnvDigest {
-    fn new(platform: &impl Platform) -> Self {
-        let env = platform.env();
+    fn new(platform: &impl Platform, ignore_env: impl Fn(&OsStr) -> bool) -> Self {
+        let mut env = Env::new();
+        for (key, value) in platform.env() {
+            if !ignore_env(key) {
+                env.insert(key, value);
+            }
+        }
 
-        PlatformEnvDigest(sha_from_env(env))
+        PlatformEnvDigest(sha_from_env(&env))
     }
 }
 
@@ -407,17 +433,57 @@ impl PathsDigest {
 
     fn add_paths(&mut self, paths: &[&Path]) -> Result<&mut Self, DigestError> {
         for path in paths {
-            let contents = fs_err::read_to_string(path)
-                .map_err(|error| DigestError::CannotReadFile(path.to_path_buf(), error))?;
+            let sha = if path.is_dir() {
+                sha_from_dir(path)?
+            } else {
+                let contents = fs_err::read_to_string(path)
+                    .map_err(|error| DigestError::CannotReadFile(path.to_path_buf(), error))?;
+
+                sha_from_string(&contents)
+            };
 
-            self.0
-                .insert(path.to_path_buf(), sha_from_string(&contents));
+            self.0.insert(path.to_path_buf(), sha);
         }
 
         Ok(self)
     }
 }
 
+/// Hashes the relative path and contents of every file in a directory
+///
+/// Files are visited in a stable (sorted) order so the same contents produce the
+/// same digest. Version control metadata in `.git` is skipped.
+fn sha_from_dir(dir: &Path) -> Result<ShaString, DigestError> {
+    let mut hasher = sha2::Sha256::new();
+    let entries = walkdir::WalkDir::new(dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| entry.file_name() != ".git");
+
+    for entry in entries {
+        let entry = entry.map_err(|error| {
+            let path = error.path().unwrap_or(dir).to_path_buf();
+            DigestError::CannotReadFile(path, error.into())
+        })?;
+
+        if entry.file_type().is_file() {
+            let path = entry.path();
+            let contents = fs_err::read(path)
+                .map_err(|error| DigestError::CannotReadFile(path.to_path_buf(), error))?;
+
+            hasher.update(
+                path.strip_prefix(dir)
+                    .unwrap_or(path)
+                    .to_string_lossy()
+                    .as_bytes(),
+            );
+            hasher.update(contents);
+        }
+    }
+
+    Ok(ShaString(format!("{:x}", hasher.finalize())))
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum DigestError {
     #[error("Attempted to read file for digest but cannot: {1}")]
@@ -506,6 +572,30 @@ mod test {
         );
     }
 
+    #[test]
+    fn metadata_platform_env_ignoring() {
+        let mut env = Env::new();
+        env.insert("PATH", "lol");
+        let one = FakeContext {
+            platform: FakePlatform { env: env.clone() },
+        };
+
+        env.insert("SECRET_TOKEN", "hunter2");
+        let two = FakeContext {
+            platform: FakePlatform { env },
+        };
+        let ignore = |key: &OsStr| key == "SECRET_TOKEN";
+
+        assert_eq!(
+            MetadataDigest::new_env_files(&one.platform, &[]).unwrap(),
+            MetadataDigest::new_env_files_ignoring(&two.platform, &[], ignore).unwrap()
+        );
+        assert!(MetadataDigest::new_env_files(&two.platform, &[])
+            .unwrap()
+            .changed(&MetadataDigest::new_env_files(&one.platform, &[]).unwrap())
+            .is_some());
+    }
+
     #[test]
     fn metadata_digest_different_file_names() {
         let tempdir = tempfile::tempdir().unwrap();
@@ -530,6 +620,36 @@ mod test {
         );
     }
 
+    #[test]
+    fn metadata_digest_dir_changed() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let engine = tempdir.path().join("engines").join("billing");
+        fs_err::create_dir_all(engine.join("lib")).unwrap();
+        fs_err::write(engine.join("billing.gemspec"), "spec").unwrap();
+        fs_err::write(engine.join("lib").join("billing.rb"), "module Billing; end").unwrap();
+        let context = FakeContext::default();
+
+        let one = MetadataDigest::new_env_files(&context.platform, &[&engine]).unwrap();
+        let two = MetadataDigest::new_env_files(&context.platform, &[&engine]).unwrap();
+        assert_eq!(None, one.changed(&two));
+
+        fs_err::create_dir_all(engine.join(".git")).unwrap();
+        fs_err::write(engine.join(".git").join("HEAD"), "ref").unwrap();
+        let three = MetadataDigest::new_env_files(&context.platform, &[&engine]).unwrap();
+        assert_eq!(None, one.changed(&three));
+
+        fs_err::write(
+            engine.join("lib").join("billing.rb"),
+            "module Billing; VERSION = 2; end",
+        )
+        .unwrap();
+        let four = MetadataDigest::new_env_files(&context.platform, &[&engine]).unwrap();
+        assert_eq!(
+            four.changed(&one).unwrap().files.unwrap(),
+            PathChange::ChangedFiles(vec![engine.clone()])
+        );
+    }
+
     #[test]
     fn metadata_digest_files_changed() {
         let tempdir = tempfile::tempdir().unwrap();
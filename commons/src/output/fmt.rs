@@ -36,6 +36,12 @@ pub fn details(contents: impl AsRef<str>) -> String {
     format!("({contents})")
 }
 
+/// Format a duration for the build output i.e. `1m 5s`
+#[must_use]
+pub fn duration(duration: &std::time::Duration) -> String {
+    time::human(duration)
+}
+
 pub(crate) const RED: &str = "\x1B[0;31m";
 pub(crate) const YELLOW: &str = "\x1B[0;33m";
 pub(crate) const CYAN: &str = "\x1B[0;36m";